@@ -0,0 +1,338 @@
+use alloc::vec::Vec;
+
+use vek::mat::repr_c::mat3::Mat3;
+
+use crate::{clamped_index_range, prelude::*, StormworksMesh, StormworksSubMesh};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3<f32>,
+    pub max: Vec3<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Vec3<f32>,
+    pub radius: f32,
+}
+
+// A box along three orthonormal axes, the point at center + sum of axes[i] * half_extents[i] is one of its corners
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Obb {
+    pub center: Vec3<f32>,
+    pub axes: [Vec3<f32>;3],
+    pub half_extents: Vec3<f32>,
+}
+
+// Everything at once, to compute a single time and keep around
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingVolumes {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+    pub obb: Obb,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MeshBounds {
+    // None for a mesh without vertices
+    pub mesh: Option<BoundingVolumes>,
+    // In sub mesh order, None for sub meshes without triangles
+    pub sub_meshes: Vec<Option<BoundingVolumes>>,
+}
+
+impl Aabb {
+    pub fn from_points(points: impl IntoIterator<Item = Vec3<f32>>) -> Option<Aabb> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        let (min, max) = points.fold((first, first), |(min, max), point| (Vec3::partial_min(min, point), Vec3::partial_max(max, point)));
+        Some(Aabb { min, max })
+    }
+
+    pub fn center(&self) -> Vec3<f32> {
+        (self.min + self.max) / 2.0
+    }
+
+    pub fn size(&self) -> Vec3<f32> {
+        self.max - self.min
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.size().product()
+    }
+
+    pub fn contains_point(&self, point: Vec3<f32>) -> bool {
+        point.x >= self.min.x && point.y >= self.min.y && point.z >= self.min.z && point.x <= self.max.x && point.y <= self.max.y && point.z <= self.max.z
+    }
+
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x && self.min.y <= other.max.y && self.min.z <= other.max.z && other.min.x <= self.max.x && other.min.y <= self.max.y && other.min.z <= self.max.z
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb { min: Vec3::partial_min(self.min, other.min), max: Vec3::partial_max(self.max, other.max) }
+    }
+
+    pub fn corners(&self) -> [Vec3<f32>;8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Vec3::new(min.x, min.y, min.z), Vec3::new(max.x, min.y, min.z), Vec3::new(min.x, max.y, min.z), Vec3::new(max.x, max.y, min.z),
+            Vec3::new(min.x, min.y, max.z), Vec3::new(max.x, min.y, max.z), Vec3::new(min.x, max.y, max.z), Vec3::new(max.x, max.y, max.z),
+        ]
+    }
+}
+
+impl BoundingSphere {
+    // Ritter's algorithm, within a few percent of the smallest sphere
+    pub fn from_points(points: &[Vec3<f32>]) -> Option<BoundingSphere> {
+        let &first = points.first()?;
+        let farthest_from = |from: Vec3<f32>| points.iter().copied().fold(from, |best, p| if p.distance_squared(from) > best.distance_squared(from) { p } else { best });
+        let a = farthest_from(first);
+        let b = farthest_from(a);
+
+        let mut sphere = BoundingSphere { center: (a + b) / 2.0, radius: a.distance(b) / 2.0 };
+        for &point in points {
+            let distance = point.distance(sphere.center);
+            if distance > sphere.radius {
+                // Grow just enough to reach the point, keeping the opposite side where it was
+                let radius = (sphere.radius + distance) / 2.0;
+                sphere.center += (point - sphere.center) * ((radius - sphere.radius) / distance);
+                sphere.radius = radius;
+            }
+        }
+        Some(sphere)
+    }
+
+    pub fn contains_point(&self, point: Vec3<f32>) -> bool {
+        point.distance_squared(self.center) <= self.radius * self.radius
+    }
+}
+
+// Eigenvectors of a symmetric matrix by cyclic Jacobi rotations, as the columns of the result
+pub(crate) fn symmetric_eigenvectors(matrix: Mat3<f32>) -> [Vec3<f32>;3] {
+    let mut v = Mat3::<f32>::identity().into_row_arrays();
+    // Scaled so the largest entry is 1, which keeps the thresholds below relative to the matrix however small or big it is
+    let scale = matrix.into_row_array().iter().fold(0.0f32, |scale, entry| scale.max(entry.abs()));
+    if scale == 0.0 || !scale.is_finite() {
+        return [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()];
+    }
+    let mut a = (matrix / scale).into_row_arrays();
+
+    for _ in 0..32 {
+        let off_diagonal = a[0][1] * a[0][1] + a[0][2] * a[0][2] + a[1][2] * a[1][2];
+        if off_diagonal < 1e-14 {
+            break;
+        }
+        for (p, q) in [(0, 1), (0, 2), (1, 2)] {
+            if a[p][q].abs() < 1e-10 {
+                continue;
+            }
+            let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+            let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+            let c = 1.0 / (t * t + 1.0).sqrt();
+            let s = t * c;
+            // A = J^T A J and V = V J, with J rotating in the p q plane
+            for row in a.iter_mut().chain(v.iter_mut()) {
+                let (rp, rq) = (row[p], row[q]);
+                row[p] = c * rp - s * rq;
+                row[q] = s * rp + c * rq;
+            }
+            let (row_p, row_q) = (a[p], a[q]);
+            a[p] = core::array::from_fn(|k| c * row_p[k] - s * row_q[k]);
+            a[q] = core::array::from_fn(|k| s * row_p[k] + c * row_q[k]);
+        }
+    }
+    [0, 1, 2].map(|column| Vec3::new(v[0][column], v[1][column], v[2][column]))
+}
+
+impl Obb {
+    // Axes from principal component analysis of the points. When that box comes out bigger than the axis aligned one, the axis aligned one is returned.
+    pub fn from_points(points: &[Vec3<f32>]) -> Option<Obb> {
+        let aabb = Aabb::from_points(points.iter().copied())?;
+        let aligned = Obb { center: aabb.center(), axes: [Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()], half_extents: aabb.size() / 2.0 };
+
+        let mean = points.iter().copied().sum::<Vec3<f32>>() / points.len() as f32;
+        let mut covariance = Mat3::<f32>::zero();
+        for &point in points {
+            let d = point - mean;
+            covariance += Mat3::new(d.x * d.x, d.x * d.y, d.x * d.z, d.y * d.x, d.y * d.y, d.y * d.z, d.z * d.x, d.z * d.y, d.z * d.z);
+        }
+        let [x, y, _] = symmetric_eigenvectors(covariance).map(Vec3::normalized);
+        // Rebuilt from the cross product so the axes stay orthonormal and right handed despite rounding
+        let z = x.cross(y).normalized();
+        let y = z.cross(x);
+        let axes = [x, y, z];
+        if axes.iter().any(|axis| !axis.x.is_finite() || !axis.y.is_finite() || !axis.z.is_finite()) {
+            return Some(aligned);
+        }
+
+        let (mut min, mut max) = (Vec3::broadcast(f32::INFINITY), Vec3::broadcast(f32::NEG_INFINITY));
+        for &point in points {
+            let local = Vec3::new(point.dot(axes[0]), point.dot(axes[1]), point.dot(axes[2]));
+            min = Vec3::partial_min(min, local);
+            max = Vec3::partial_max(max, local);
+        }
+        let middle = (min + max) / 2.0;
+        let fitted = Obb { center: axes[0] * middle.x + axes[1] * middle.y + axes[2] * middle.z, axes, half_extents: (max - min) / 2.0 };
+
+        Some(if fitted.volume() < aligned.volume() { fitted } else { aligned })
+    }
+
+    pub fn volume(&self) -> f32 {
+        self.half_extents.product() * 8.0
+    }
+
+    pub fn contains_point(&self, point: Vec3<f32>) -> bool {
+        let d = point - self.center;
+        (0..3).all(|i| d.dot(self.axes[i]).abs() <= self.half_extents[i])
+    }
+
+    pub fn corners(&self) -> [Vec3<f32>;8] {
+        let [x, y, z] = [0, 1, 2].map(|i| self.axes[i] * self.half_extents[i]);
+        [
+            self.center - x - y - z, self.center + x - y - z, self.center - x + y - z, self.center + x + y - z,
+            self.center - x - y + z, self.center + x - y + z, self.center - x + y + z, self.center + x + y + z,
+        ]
+    }
+}
+
+impl BoundingVolumes {
+    pub fn from_points(points: &[Vec3<f32>]) -> Option<BoundingVolumes> {
+        Some(BoundingVolumes {
+            aabb: Aabb::from_points(points.iter().copied())?,
+            sphere: BoundingSphere::from_points(points)?,
+            obb: Obb::from_points(points)?,
+        })
+    }
+}
+
+impl StormworksSubMesh {
+    // The bounds as written in the file, to compare against what the vertices say
+    pub fn stored_aabb(&self) -> Aabb {
+        Aabb { min: self.bounds_min, max: self.bounds_max }
+    }
+}
+
+impl StormworksMesh {
+    // Each vertex the sub mesh's triangles use, once
+    pub fn sub_mesh_positions(&self, sub_mesh: usize) -> Vec<Vec3<f32>> {
+        let Some(sub_mesh) = self.sub_meshes.get(sub_mesh) else {
+            return Vec::new();
+        };
+        let mut seen = alloc::vec![false; self.vertices.len()];
+        let mut positions = Vec::new();
+        for &index in &self.indices[clamped_index_range(sub_mesh, self.indices.len())] {
+            if let Some(seen) = seen.get_mut(index as usize) {
+                if !*seen {
+                    *seen = true;
+                    positions.push(self.vertices[index as usize].position);
+                }
+            }
+        }
+        positions
+    }
+
+    fn all_positions(&self) -> Vec<Vec3<f32>> {
+        self.vertices.iter().map(|vertex| vertex.position).collect()
+    }
+
+    // Whole mesh versions cover every vertex, None without any
+    pub fn aabb(&self) -> Option<Aabb> {
+        Aabb::from_points(self.vertices.iter().map(|vertex| vertex.position))
+    }
+
+    pub fn bounding_sphere(&self) -> Option<BoundingSphere> {
+        BoundingSphere::from_points(&self.all_positions())
+    }
+
+    pub fn obb(&self) -> Option<Obb> {
+        Obb::from_points(&self.all_positions())
+    }
+
+    // Sub mesh versions cover the vertices of that sub mesh's triangles, None if it has none or doesn't exist
+    pub fn sub_mesh_aabb(&self, sub_mesh: usize) -> Option<Aabb> {
+        Aabb::from_points(self.sub_mesh_positions(sub_mesh))
+    }
+
+    pub fn sub_mesh_bounding_sphere(&self, sub_mesh: usize) -> Option<BoundingSphere> {
+        BoundingSphere::from_points(&self.sub_mesh_positions(sub_mesh))
+    }
+
+    pub fn sub_mesh_obb(&self, sub_mesh: usize) -> Option<Obb> {
+        Obb::from_points(&self.sub_mesh_positions(sub_mesh))
+    }
+
+    pub fn bounds(&self) -> MeshBounds {
+        MeshBounds {
+            mesh: BoundingVolumes::from_points(&self.all_positions()),
+            sub_meshes: (0..self.sub_meshes.len()).map(|i| BoundingVolumes::from_points(&self.sub_mesh_positions(i))).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, primitive_sphere, PrimitiveStyle};
+    use vek::{mat::repr_c::mat4::Mat4, quaternion::repr_c::Quaternion};
+
+    fn assert_close(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!(a.distance(b) < 1e-4, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn aabb_of_a_box() {
+        let mesh = primitive_box(Vec3::new(4, 2, 8), PrimitiveStyle::default()).unwrap();
+        let aabb = mesh.aabb().unwrap();
+        assert_close(aabb.size(), Vec3::new(1.0, 0.5, 2.0));
+        assert_eq!(mesh.sub_mesh_aabb(0), Some(aabb));
+        assert_eq!(mesh.sub_meshes[0].stored_aabb(), aabb);
+        assert!((aabb.volume() - 1.0).abs() < 1e-6);
+        assert!(aabb.contains_point(aabb.center()) && !aabb.contains_point(aabb.max + Vec3::unit_x()));
+
+        let other = Aabb { min: aabb.max - Vec3::broadcast(0.1), max: aabb.max + Vec3::one() };
+        assert!(aabb.intersects(&other));
+        assert_eq!(aabb.union(&other), Aabb { min: aabb.min, max: other.max });
+    }
+
+    #[test]
+    fn sphere_contains_every_vertex() {
+        let mesh = primitive_sphere(1.0, 24, 12, PrimitiveStyle::default()).unwrap();
+        let sphere = mesh.bounding_sphere().unwrap();
+        // Ritter's sphere isn't minimal, but it's close for a sphere
+        assert!(sphere.radius >= 1.0 - 1e-4 && sphere.radius < 1.05, "{}", sphere.radius);
+        assert!(mesh.vertices.iter().all(|vertex| vertex.position.distance(sphere.center) <= sphere.radius + 1e-5));
+    }
+
+    #[test]
+    fn obb_follows_a_rotated_box() {
+        let mut mesh = primitive_box(Vec3::new(8, 4, 2), PrimitiveStyle::default()).unwrap();
+        mesh.center_on(crate::MeshAnchor::BoundsCenter);
+        let rotation = Quaternion::rotation_z(0.5) * Quaternion::rotation_x(0.3);
+        mesh.transform(Mat4::from(rotation));
+
+        let obb = mesh.obb().unwrap();
+        assert!((obb.volume() - 2.0 * 1.0 * 0.5).abs() < 1e-3, "{}", obb.volume());
+        assert!(obb.volume() < mesh.aabb().unwrap().volume());
+        assert_close(obb.center, Vec3::zero());
+        for vertex in &mesh.vertices {
+            let local = vertex.position - obb.center;
+            for (axis, half_extent) in obb.axes.iter().zip(obb.half_extents) {
+                assert!(local.dot(*axis).abs() <= half_extent + 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_meshes_and_sub_meshes_have_no_bounds() {
+        let empty = StormworksMesh::default();
+        assert_eq!(empty.aabb(), None);
+        assert_eq!(empty.bounds(), MeshBounds::default());
+
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        mesh.sub_meshes[0].index_buffer_length = 0;
+        let bounds = mesh.bounds();
+        assert!(bounds.mesh.is_some());
+        assert_eq!(bounds.sub_meshes, alloc::vec![None]);
+        assert_eq!(mesh.sub_mesh_obb(1), None);
+    }
+}
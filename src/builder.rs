@@ -0,0 +1,182 @@
+use alloc::{string::String, vec::Vec};
+
+use vek::{vec::repr_c::vec3::Vec3, Rgba};
+
+use crate::{MeshFormatVersion, StormworksMesh, StormworksMeshVertexRecord, StormworksParserError, StormworksShaderType, StormworksSubMesh};
+
+// Builds a StormworksMesh without having to keep the counts, name lengths and sub mesh ranges in sync by hand.
+// Triangles belong to the sub mesh begun most recently. Ones added before the first begin_sub_mesh aren't in any sub mesh, so the game won't draw them.
+#[derive(Clone, Debug)]
+pub struct StormworksMeshBuilder {
+    version: MeshFormatVersion,
+    vertices: Vec<StormworksMeshVertexRecord>,
+    indices: Vec<u32>,
+    sub_meshes: Vec<(String, StormworksShaderType, u32)>,
+}
+
+impl Default for StormworksMeshBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StormworksMeshBuilder {
+    pub fn new() -> Self {
+        Self::with_version(MeshFormatVersion::V7)
+    }
+
+    pub fn with_version(version: MeshFormatVersion) -> Self {
+        StormworksMeshBuilder {
+            version,
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            sub_meshes: Vec::new(),
+        }
+    }
+
+    // Returns the index to use in add_triangle
+    pub fn add_vertex(&mut self, position: Vec3<f32>, color: Rgba<u8>, normal: Vec3<f32>) -> u32 {
+        self.vertices.push(StormworksMeshVertexRecord { position, color, normal });
+        self.vertices.len() as u32 - 1
+    }
+
+    // Clockwise when looking at the front face, like the game's own meshes
+    pub fn add_triangle(&mut self, a: u32, b: u32, c: u32) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    pub fn begin_sub_mesh(&mut self, name: impl Into<String>, shader: StormworksShaderType) {
+        self.sub_meshes.push((name.into(), shader, self.indices.len() as u32));
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertices.len()
+    }
+
+    pub fn index_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    // Counts, name lengths, sub mesh ranges and bounds are all derived here, and the result is checked like a parsed file.
+    // The sub mesh header fields nobody knows the meaning of are left at zero.
+    pub fn build(self) -> Result<StormworksMesh, StormworksParserError> {
+        let [header0, header1, header3, header4] = self.version.headers();
+        let index_count = self.indices.len() as u32;
+
+        let mut sub_meshes = Vec::with_capacity(self.sub_meshes.len());
+        // Each sub mesh runs until the next one begins
+        let ends: Vec<u32> = self.sub_meshes.iter().skip(1).map(|&(_, _, start)| start).chain([index_count]).collect();
+        for ((name, shader_id, start), end) in self.sub_meshes.into_iter().zip(ends) {
+
+            let mut bounds = None;
+            for &index in &self.indices[start as usize..end as usize] {
+                // Out of range indices are reported by validate below
+                if let Some(vertex) = self.vertices.get(index as usize) {
+                    bounds = Some(match bounds {
+                        None => (vertex.position, vertex.position),
+                        Some((min, max)) => (Vec3::partial_min(min, vertex.position), Vec3::partial_max(max, vertex.position)),
+                    });
+                }
+            }
+            let (bounds_min, bounds_max) = bounds.unwrap_or((Vec3::zero(), Vec3::zero()));
+
+            sub_meshes.push(StormworksSubMesh {
+                index_buffer_start: start,
+                index_buffer_length: end - start,
+                header2: 0,
+                shader_id,
+                bounds_min,
+                bounds_max,
+                header6: 0,
+                name_length_bytes: name.len().min(u16::MAX as usize) as u16,
+                name,
+                header8: Vec3::zero(),
+            });
+        }
+
+        let mesh = StormworksMesh {
+            header0,
+            header1,
+            header3,
+            header4,
+            vertex_count: self.vertices.len().min(u32::MAX as usize) as u32,
+            vertices: self.vertices,
+            index_count,
+            indices: self.indices,
+            sub_mesh_count: sub_meshes.len().min(u32::MAX as usize) as u32,
+            sub_meshes,
+        };
+        mesh.validate()?;
+        Ok(mesh)
+    }
+}
+
+impl StormworksMesh {
+    pub fn builder() -> StormworksMeshBuilder {
+        StormworksMeshBuilder::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_a_hand_built_mesh() {
+        let vertex = |x: f32, y: f32| StormworksMeshVertexRecord { position: Vec3::new(x, y, 0.0), color: Rgba::white(), normal: -Vec3::unit_z() };
+        let mut builder = StormworksMesh::builder();
+        builder.begin_sub_mesh("quad", StormworksShaderType::Opaque);
+        let corners: Vec<u32> = [(0.0, 0.0), (0.0, 1.0), (1.0, 1.0), (1.0, 0.0)].map(|(x, y)| builder.add_vertex(Vec3::new(x, y, 0.0), Rgba::white(), -Vec3::unit_z())).into();
+        builder.add_triangle(corners[0], corners[1], corners[2]);
+        builder.add_triangle(corners[0], corners[2], corners[3]);
+        builder.begin_sub_mesh("glow", StormworksShaderType::Emissive);
+        let top = builder.add_vertex(Vec3::new(0.5, 2.0, 0.0), Rgba::white(), -Vec3::unit_z());
+        builder.add_triangle(corners[1], top, corners[2]);
+        assert_eq!((builder.vertex_count(), builder.index_count()), (5, 9));
+
+        let sub_mesh = |start: u32, length: u32, shader_id: StormworksShaderType, bounds_min: Vec3<f32>, bounds_max: Vec3<f32>, name: &str| StormworksSubMesh {
+            index_buffer_start: start,
+            index_buffer_length: length,
+            header2: 0,
+            shader_id,
+            bounds_min,
+            bounds_max,
+            header6: 0,
+            name_length_bytes: name.len() as u16,
+            name: name.into(),
+            header8: Vec3::zero(),
+        };
+        let [header0, header1, header3, header4] = MeshFormatVersion::V7.headers();
+        let expected = StormworksMesh {
+            header0,
+            header1,
+            header3,
+            header4,
+            vertex_count: 5,
+            vertices: alloc::vec![vertex(0.0, 0.0), vertex(0.0, 1.0), vertex(1.0, 1.0), vertex(1.0, 0.0), vertex(0.5, 2.0)],
+            index_count: 9,
+            indices: alloc::vec![0, 1, 2, 0, 2, 3, 1, 4, 2],
+            sub_mesh_count: 2,
+            sub_meshes: alloc::vec![
+                sub_mesh(0, 6, StormworksShaderType::Opaque, Vec3::zero(), Vec3::new(1.0, 1.0, 0.0), "quad"),
+                sub_mesh(6, 3, StormworksShaderType::Emissive, Vec3::new(0.0, 1.0, 0.0), Vec3::new(1.0, 2.0, 0.0), "glow"),
+            ],
+        };
+
+        let mesh = builder.build().unwrap();
+        assert_eq!(mesh, expected);
+        mesh.validate().unwrap();
+    }
+
+    #[test]
+    fn invalid_meshes_fail_to_build() {
+        let mut builder = StormworksMeshBuilder::new();
+        builder.begin_sub_mesh("", StormworksShaderType::Opaque);
+        let a = builder.add_vertex(Vec3::zero(), Rgba::white(), Vec3::unit_y());
+        builder.add_triangle(a, a, a + 1);
+        assert!(builder.build().is_err());
+
+        // Nothing added is still a valid mesh
+        assert_eq!(StormworksMeshBuilder::new().build().unwrap().sub_meshes, []);
+    }
+}
@@ -0,0 +1,446 @@
+use alloc::vec::Vec;
+
+use vek::Rgba;
+
+use crate::{clamped_index_range, prelude::*, normals::triangle_normal, Aabb, StormworksMesh, StormworksShaderType};
+
+const MAX_LEAF_TRIANGLES: usize = 4;
+const SAH_BINS: usize = 12;
+// Relative cost of testing one triangle vs stepping into one more node
+const TRIANGLE_COST: f32 = 1.0;
+const TRAVERSAL_COST: f32 = 1.0;
+const NO_SUB_MESH: u32 = u32::MAX;
+const BARYCENTRIC_SLACK: f32 = 1e-5;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct BvhNode {
+    pub(crate) aabb: Aabb,
+    // For leaves the first entry in triangles, otherwise the index of the second child (the first always directly follows its parent)
+    pub(crate) start: u32,
+    // 0 for inner nodes
+    pub(crate) count: u32,
+}
+
+// Bounding volume hierarchy over a mesh's triangles, built with the surface area heuristic.
+// It only keeps triangle ids, so queries take the mesh it was built from. After moving vertices call refit instead of rebuilding,
+// which stays correct for any change and stays fast as long as the mesh moves mostly as a whole.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MeshBvh {
+    pub(crate) nodes: Vec<BvhNode>,
+    pub(crate) triangles: Vec<u32>,
+    triangle_sub_meshes: Vec<u32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    // Along the normalized direction, so in mesh units
+    pub distance: f32,
+    pub position: Vec3<f32>,
+    pub triangle: u32,
+    // Weights of the triangle's three corners, in index order
+    pub barycentrics: Vec3<f32>,
+    // Interpolated from the vertex normals
+    pub normal: Vec3<f32>,
+    pub color: Rgba<u8>,
+    // Whether the ray came from the side the triangle faces
+    pub front_face: bool,
+    // None for triangles outside of every sub mesh
+    pub sub_mesh: Option<u32>,
+    pub shader: Option<StormworksShaderType>,
+}
+
+pub(crate) fn triangle_positions(mesh: &StormworksMesh, triangle: u32) -> [Vec3<f32>;3] {
+    let first = triangle as usize * 3;
+    [0, 1, 2].map(|k| mesh.vertices[mesh.indices[first + k] as usize].position)
+}
+
+fn triangle_aabb(mesh: &StormworksMesh, triangle: u32) -> Aabb {
+    let [a, b, c] = triangle_positions(mesh, triangle);
+    Aabb { min: Vec3::partial_min(Vec3::partial_min(a, b), c), max: Vec3::partial_max(Vec3::partial_max(a, b), c) }
+}
+
+fn surface_area(aabb: &Aabb) -> f32 {
+    let size = aabb.size().map(|s| s.max(0.0));
+    2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+}
+
+fn empty_aabb() -> Aabb {
+    Aabb { min: Vec3::broadcast(f32::INFINITY), max: Vec3::broadcast(f32::NEG_INFINITY) }
+}
+
+fn sah_bin(centroid: f32, low: f32, high: f32) -> usize {
+    (((centroid - low) / (high - low) * SAH_BINS as f32) as usize).min(SAH_BINS - 1)
+}
+
+// Slab test, returns the distance where the ray enters the box if it does so before max_distance
+pub(crate) fn ray_aabb(aabb: &Aabb, origin: Vec3<f32>, inverse_direction: Vec3<f32>, max_distance: f32) -> Option<f32> {
+    let (mut near, mut far) = (0.0f32, max_distance);
+    for axis in [0, 1, 2] {
+        if inverse_direction[axis].is_infinite() {
+            // Parallel to this axis' slabs, 0 * inf would give NaN, so just check which side of them the ray runs
+            if origin[axis] < aabb.min[axis] || origin[axis] > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t1 = (aabb.min[axis] - origin[axis]) * inverse_direction[axis];
+        let t2 = (aabb.max[axis] - origin[axis]) * inverse_direction[axis];
+        near = near.max(t1.min(t2));
+        far = far.min(t1.max(t2));
+    }
+    if near <= far { Some(near) } else { None }
+}
+
+// Möller Trumbore, hitting both sides. Returns the distance and the weights of b and c.
+// Edges get a little slack, otherwise rays through a shared vertex or edge (easy to do on the block grid) can slip between triangles.
+pub(crate) fn ray_triangle(origin: Vec3<f32>, direction: Vec3<f32>, [a, b, c]: [Vec3<f32>;3]) -> Option<(f32, f32, f32)> {
+    let (edge1, edge2) = (b - a, c - a);
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON * edge1.magnitude() * edge2.magnitude() || !determinant.is_finite() {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let s = origin - a;
+    let u = s.dot(p) * inverse;
+    if !(-BARYCENTRIC_SLACK..=1.0 + BARYCENTRIC_SLACK).contains(&u) {
+        return None;
+    }
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inverse;
+    if v < -BARYCENTRIC_SLACK || u + v > 1.0 + BARYCENTRIC_SLACK {
+        return None;
+    }
+    let t = edge2.dot(q) * inverse;
+    if t >= 0.0 { Some((t, u, v)) } else { None }
+}
+
+impl MeshBvh {
+    pub fn build(mesh: &StormworksMesh) -> MeshBvh {
+        Self::build_from(mesh, |_| true)
+    }
+
+    // Only the triangles of one sub mesh, so every query answers for just that sub mesh
+    pub fn build_for_sub_mesh(mesh: &StormworksMesh, sub_mesh: usize) -> MeshBvh {
+        Self::build_from(mesh, |owner| owner == sub_mesh as u32)
+    }
+
+    fn build_from(mesh: &StormworksMesh, include: impl Fn(u32) -> bool) -> MeshBvh {
+        let triangle_count = mesh.indices.len() / 3;
+        let aabbs: Vec<Aabb> = (0..triangle_count as u32).map(|triangle| triangle_aabb(mesh, triangle)).collect();
+        let centroids: Vec<Vec3<f32>> = aabbs.iter().map(Aabb::center).collect();
+
+        // A triangle goes with the first sub mesh containing it, same as StormworksMesh::merge
+        let triangle_sub_meshes: Vec<u32> = (0..triangle_count)
+            .map(|triangle| {
+                let first = triangle * 3;
+                mesh.sub_meshes
+                    .iter()
+                    .position(|sub_mesh| clamped_index_range(sub_mesh, mesh.indices.len()).contains(&first))
+                    .map_or(NO_SUB_MESH, |i| i as u32)
+            })
+            .collect();
+
+        let triangles: Vec<u32> = (0..triangle_count as u32).filter(|&triangle| include(triangle_sub_meshes[triangle as usize])).collect();
+        let count = triangles.len();
+        let mut bvh = MeshBvh { nodes: Vec::with_capacity(count * 2), triangles, triangle_sub_meshes };
+        // No triangles means no nodes at all, a root with count 0 would look like an inner node
+        if count > 0 {
+            bvh.build_node(&aabbs, &centroids, 0, count);
+        }
+        bvh
+    }
+
+    fn build_node(&mut self, aabbs: &[Aabb], centroids: &[Vec3<f32>], start: usize, end: usize) {
+        let triangles = &mut self.triangles[start..end];
+        let aabb = triangles.iter().fold(empty_aabb(), |aabb, &triangle| aabb.union(&aabbs[triangle as usize]));
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode { aabb, start: start as u32, count: (end - start) as u32 });
+
+        if triangles.len() <= MAX_LEAF_TRIANGLES {
+            return;
+        }
+
+        // Binned SAH over the centroids, on all three axes
+        let centroid_bounds = Aabb::from_points(triangles.iter().map(|&triangle| centroids[triangle as usize])).unwrap_or(aabb);
+        let mut best: Option<(f32, usize, usize)> = None;
+        for axis in [0, 1, 2] {
+            let (low, high) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+            // Also skips NaN bounds from broken positions
+            if high - low <= 0.0 || (high - low).is_nan() {
+                continue;
+            }
+
+            let mut bins = [(empty_aabb(), 0usize); SAH_BINS];
+            for &triangle in triangles.iter() {
+                let bin = &mut bins[sah_bin(centroids[triangle as usize][axis], low, high)];
+                bin.0 = bin.0.union(&aabbs[triangle as usize]);
+                bin.1 += 1;
+            }
+
+            for split in 1..SAH_BINS {
+                let (left, right) = bins.split_at(split);
+                let side = |bins: &[(Aabb, usize)]| bins.iter().fold((empty_aabb(), 0), |(aabb, count), bin| (aabb.union(&bin.0), count + bin.1));
+                let ((left_aabb, left_count), (right_aabb, right_count)) = (side(left), side(right));
+                if left_count == 0 || right_count == 0 {
+                    continue;
+                }
+                let cost = surface_area(&left_aabb) * left_count as f32 + surface_area(&right_aabb) * right_count as f32;
+                if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                    best = Some((cost, axis, split));
+                }
+            }
+        }
+
+        let Some((cost, axis, split)) = best else {
+            // Every centroid in the same spot, nothing to split on
+            return;
+        };
+        let parent_area = surface_area(&aabb);
+        let leaf_cost = triangles.len() as f32 * TRIANGLE_COST;
+        let split_cost = TRAVERSAL_COST + TRIANGLE_COST * cost / parent_area;
+        if parent_area > 0.0 && split_cost >= leaf_cost {
+            return;
+        }
+
+        // Same binning as above so the partition matches the evaluated split exactly
+        let (low, high) = (centroid_bounds.min[axis], centroid_bounds.max[axis]);
+        let mut middle = 0;
+        for i in 0..triangles.len() {
+            if sah_bin(centroids[triangles[i] as usize][axis], low, high) < split {
+                triangles.swap(i, middle);
+                middle += 1;
+            }
+        }
+
+        self.nodes[node].count = 0;
+        self.build_node(aabbs, centroids, start, start + middle);
+        self.nodes[node].start = self.nodes.len() as u32;
+        self.build_node(aabbs, centroids, start + middle, end);
+    }
+
+    // Recomputes every box bottom up from the mesh's current positions. The mesh must still have the same triangles.
+    pub fn refit(&mut self, mesh: &StormworksMesh) {
+        // Children always come after their parent, so going backwards visits them first
+        for i in (0..self.nodes.len()).rev() {
+            let node = self.nodes[i];
+            self.nodes[i].aabb = if node.count > 0 {
+                self.triangles[node.start as usize..(node.start + node.count) as usize]
+                    .iter()
+                    .fold(empty_aabb(), |aabb, &triangle| aabb.union(&triangle_aabb(mesh, triangle)))
+            } else {
+                self.nodes[i + 1].aabb.union(&self.nodes[node.start as usize].aabb)
+            };
+        }
+    }
+
+    pub fn aabb(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.aabb)
+    }
+
+    pub fn sub_mesh_of(&self, triangle: u32) -> Option<u32> {
+        self.triangle_sub_meshes.get(triangle as usize).copied().filter(|&sub_mesh| sub_mesh != NO_SUB_MESH)
+    }
+
+    // Walks the tree front to back along the ray. on_triangle gets each candidate triangle and the current distance limit, and returns a new one.
+    pub(crate) fn traverse_ray(&self, mesh: &StormworksMesh, origin: Vec3<f32>, direction: Vec3<f32>, mut max_distance: f32, mut on_triangle: impl FnMut(u32, f32, f32, f32) -> Option<f32>) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        let inverse_direction = direction.map(|d| 1.0 / d);
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0u32);
+
+        while let Some(index) = stack.pop() {
+            let node = self.nodes[index as usize];
+            if ray_aabb(&node.aabb, origin, inverse_direction, max_distance).is_none() {
+                continue;
+            }
+            if node.count > 0 {
+                for &triangle in &self.triangles[node.start as usize..(node.start + node.count) as usize] {
+                    if let Some((distance, u, v)) = ray_triangle(origin, direction, triangle_positions(mesh, triangle)) {
+                        if distance <= max_distance {
+                            match on_triangle(triangle, distance, u, v) {
+                                Some(limit) => max_distance = limit,
+                                None => return,
+                            }
+                        }
+                    }
+                }
+                continue;
+            }
+
+            // The nearer child goes on the stack last so it's searched first
+            let (left, right) = (index + 1, node.start);
+            let entry = |child: u32| ray_aabb(&self.nodes[child as usize].aabb, origin, inverse_direction, max_distance);
+            match (entry(left), entry(right)) {
+                (Some(left_entry), Some(right_entry)) if right_entry < left_entry => {
+                    stack.push(left);
+                    stack.push(right);
+                }
+                (left_entry, right_entry) => {
+                    if right_entry.is_some() {
+                        stack.push(right);
+                    }
+                    if left_entry.is_some() {
+                        stack.push(left);
+                    }
+                }
+            }
+        }
+    }
+
+    // Closest hit within max_distance. Both sides of triangles are hit, front_face tells which one it was.
+    pub fn raycast(&self, mesh: &StormworksMesh, origin: Vec3<f32>, direction: Vec3<f32>, max_distance: f32) -> Option<RayHit> {
+        let direction = direction.normalized();
+        if !direction.x.is_finite() || !direction.y.is_finite() || !direction.z.is_finite() {
+            return None;
+        }
+
+        let mut closest: Option<(u32, f32, f32, f32)> = None;
+        self.traverse_ray(mesh, origin, direction, max_distance, |triangle, distance, u, v| {
+            if closest.is_none_or(|(_, best, _, _)| distance < best) {
+                closest = Some((triangle, distance, u, v));
+            }
+            Some(distance)
+        });
+
+        let (triangle, distance, u, v) = closest?;
+        let barycentrics = Vec3::new(1.0 - u - v, u, v);
+        let first = triangle as usize * 3;
+        let corners = [0, 1, 2].map(|k| &mesh.vertices[mesh.indices[first + k] as usize]);
+
+        let normal = (corners[0].normal * barycentrics.x + corners[1].normal * barycentrics.y + corners[2].normal * barycentrics.z).normalized();
+        let color = corners[0].color.map(|c| c as f32) * barycentrics.x + corners[1].color.map(|c| c as f32) * barycentrics.y + corners[2].color.map(|c| c as f32) * barycentrics.z;
+        let sub_mesh = self.sub_mesh_of(triangle);
+
+        Some(RayHit {
+            distance,
+            position: origin + direction * distance,
+            triangle,
+            barycentrics,
+            normal,
+            color: color.map(|c| c.round().clamp(0.0, 255.0) as u8),
+            front_face: triangle_normal(corners[0].position, corners[1].position, corners[2].position).dot(direction) < 0.0,
+            sub_mesh,
+            shader: sub_mesh.map(|i| mesh.sub_meshes[i as usize].shader_id),
+        })
+    }
+
+    // Whether anything is hit within max_distance, stopping at the first triangle found. For shadow and line of sight checks.
+    pub fn any_hit(&self, mesh: &StormworksMesh, origin: Vec3<f32>, direction: Vec3<f32>, max_distance: f32) -> bool {
+        let direction = direction.normalized();
+        if !direction.x.is_finite() || !direction.y.is_finite() || !direction.z.is_finite() {
+            return false;
+        }
+
+        let mut hit = false;
+        self.traverse_ray(mesh, origin, direction, max_distance, |_, _, _, _| {
+            hit = true;
+            None
+        });
+        hit
+    }
+}
+
+impl StormworksMesh {
+    pub fn build_bvh(&self) -> MeshBvh {
+        MeshBvh::build(self)
+    }
+
+    pub fn build_sub_mesh_bvh(&self, sub_mesh: usize) -> MeshBvh {
+        MeshBvh::build_for_sub_mesh(self, sub_mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, primitive_sphere, MeshAnchor, PrimitiveStyle};
+    use vek::{mat::repr_c::mat4::Mat4, quaternion::repr_c::Quaternion};
+
+    fn brute_force(mesh: &StormworksMesh, origin: Vec3<f32>, direction: Vec3<f32>) -> Option<(u32, f32)> {
+        let direction = direction.normalized();
+        (0..mesh.indices.len() as u32 / 3)
+            .filter_map(|triangle| ray_triangle(origin, direction, triangle_positions(mesh, triangle)).map(|(distance, _, _)| (triangle, distance)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    }
+
+    // Small deterministic spread of rays, all starting outside of a unit sphere
+    fn rays() -> impl Iterator<Item = (Vec3<f32>, Vec3<f32>)> {
+        (0..200).map(|i| {
+            let i = i as f32;
+            let origin = Vec3::new((i * 0.37).sin(), (i * 0.71).cos(), (i * 0.13).sin()).normalized() * 3.0;
+            let target = Vec3::new((i * 1.3).cos(), (i * 0.9).sin(), (i * 2.1).cos()) * 1.2;
+            (origin, target - origin)
+        })
+    }
+
+    #[test]
+    fn raycast_matches_brute_force() {
+        let mesh = primitive_sphere(1.0, 24, 12, PrimitiveStyle::default()).unwrap();
+        let bvh = mesh.build_bvh();
+        let mut hits = 0;
+        for (origin, direction) in rays() {
+            let hit = bvh.raycast(&mesh, origin, direction, f32::INFINITY);
+            let expected = brute_force(&mesh, origin, direction);
+            assert_eq!(hit.is_some(), expected.is_some(), "{origin:?} {direction:?}");
+            assert_eq!(bvh.any_hit(&mesh, origin, direction, f32::INFINITY), expected.is_some());
+            if let (Some(hit), Some((_, distance))) = (hit, expected) {
+                hits += 1;
+                // Ties between triangles sharing an edge can pick either, so only compare distances
+                assert!((hit.distance - distance).abs() < 1e-5, "{} != {distance}", hit.distance);
+                assert!((hit.position.magnitude() - 1.0).abs() < 0.05);
+                assert!(hit.front_face);
+                assert_eq!(hit.sub_mesh, Some(0));
+            }
+        }
+        assert!(hits > 50 && hits < 200, "{hits}");
+    }
+
+    #[test]
+    fn max_distance_and_back_faces() {
+        let mut mesh = primitive_box(Vec3::new(4, 4, 4), PrimitiveStyle::default()).unwrap();
+        mesh.center_on(MeshAnchor::BoundsCenter);
+        let bvh = mesh.build_bvh();
+
+        let hit = bvh.raycast(&mesh, Vec3::new(0.0, 0.0, -2.0), Vec3::unit_z(), 10.0).unwrap();
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+        assert!(hit.front_face);
+        assert!(bvh.raycast(&mesh, Vec3::new(0.0, 0.0, -2.0), Vec3::unit_z(), 1.0).is_none());
+        assert!(!bvh.any_hit(&mesh, Vec3::new(0.0, 0.0, -2.0), Vec3::unit_z(), 1.0));
+        assert!(bvh.raycast(&mesh, Vec3::new(0.0, 0.0, -2.0), Vec3::zero(), 10.0).is_none());
+
+        // From the inside the far wall is hit from behind
+        let hit = bvh.raycast(&mesh, Vec3::zero(), Vec3::unit_z(), 10.0).unwrap();
+        assert!((hit.distance - 0.5).abs() < 1e-5);
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn refit_follows_moved_vertices() {
+        let mut mesh = primitive_sphere(1.0, 16, 8, PrimitiveStyle::default()).unwrap();
+        let mut bvh = mesh.build_bvh();
+        mesh.transform(Mat4::<f32>::translation_3d(Vec3::new(5.0, 0.0, 0.0)) * Mat4::from(Quaternion::rotation_y(0.4)));
+        bvh.refit(&mesh);
+        assert_eq!(bvh.aabb(), mesh.aabb());
+        for (origin, direction) in rays() {
+            let origin = origin + Vec3::new(5.0, 0.0, 0.0);
+            let expected = brute_force(&mesh, origin, direction).map(|(_, distance)| distance);
+            let distance = bvh.raycast(&mesh, origin, direction, f32::INFINITY).map(|hit| hit.distance);
+            assert_eq!(distance.is_some(), expected.is_some());
+            if let (Some(distance), Some(expected)) = (distance, expected) {
+                assert!((distance - expected).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn empty_mesh() {
+        let mesh = StormworksMesh::default();
+        let bvh = mesh.build_bvh();
+        assert_eq!(bvh.aabb(), None);
+        assert!(bvh.raycast(&mesh, Vec3::zero(), Vec3::unit_x(), f32::INFINITY).is_none());
+        assert!(!bvh.any_hit(&mesh, Vec3::zero(), Vec3::unit_x(), f32::INFINITY));
+    }
+}
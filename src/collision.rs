@@ -0,0 +1,258 @@
+use alloc::vec::Vec;
+
+use vek::{mat::repr_c::mat4::Mat4, vec::repr_c::vec3::Vec3};
+
+use crate::{
+    bvh::{ray_triangle, triangle_positions},
+    normals::triangle_normal,
+    queries::{closest_point_on_triangle, segment_segment_distance_squared},
+    Aabb, MeshBvh, SingularTransform, StormworksMesh, StormworksParserError,
+};
+
+// A mesh with its BVH, placed in the world. The BVH is in the mesh's own space, so moving the mesh around only means changing transform.
+// Queries run in the space of the mesh they're called on, so that one's transform has to be invertible. The other one's may flatten.
+#[derive(Clone, Copy, Debug)]
+pub struct PlacedMesh<'a> {
+    pub mesh: &'a StormworksMesh,
+    pub bvh: &'a MeshBvh,
+    pub transform: Mat4<f32>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TrianglePair {
+    pub a: u32,
+    pub b: u32,
+    // Where the two triangles cross, in world space. None when they lie in the same plane and overlap in an area rather than along a segment.
+    pub segment: Option<[Vec3<f32>;2]>,
+}
+
+impl<'a> PlacedMesh<'a> {
+    pub fn new(mesh: &'a StormworksMesh, bvh: &'a MeshBvh, transform: Mat4<f32>) -> Self {
+        PlacedMesh { mesh, bvh, transform }
+    }
+}
+
+fn transformed_aabb(aabb: &Aabb, transform: Mat4<f32>) -> Aabb {
+    let corners = aabb.corners().map(|corner| transform.mul_point(corner));
+    Aabb::from_points(corners).unwrap_or(*aabb)
+}
+
+fn surface_area(aabb: &Aabb) -> f32 {
+    let size = aabb.size();
+    size.x * size.y + size.y * size.z + size.z * size.x
+}
+
+fn coplanar_overlap(a: [Vec3<f32>;3], b: [Vec3<f32>;3], tolerance: f32) -> bool {
+    let tolerance_squared = tolerance * tolerance;
+    let edges = |t: [Vec3<f32>;3]| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])];
+    edges(a).iter().any(|&(p1, q1)| edges(b).iter().any(|&(p2, q2)| segment_segment_distance_squared(p1, q1, p2, q2) <= tolerance_squared))
+        || a.iter().any(|&corner| closest_point_on_triangle(corner, b).0.distance_squared(corner) <= tolerance_squared)
+        || b.iter().any(|&corner| closest_point_on_triangle(corner, a).0.distance_squared(corner) <= tolerance_squared)
+}
+
+// Both triangles in the same space. Returns None when they don't touch, Some(None) for coplanar overlap, otherwise the crossing segment.
+fn triangle_intersection(a: [Vec3<f32>;3], b: [Vec3<f32>;3]) -> Option<Option<[Vec3<f32>;2]>> {
+    let (normal_a, normal_b) = (triangle_normal(a[0], a[1], a[2]), triangle_normal(b[0], b[1], b[2]));
+    let scale = a.iter().chain(b.iter()).fold(0.0f32, |scale, corner| scale.max(corner.x.abs()).max(corner.y.abs()).max(corner.z.abs())).max(1.0);
+    let tolerance = scale * 1e-6;
+
+    // Quick reject, every corner of one triangle strictly on one side of the other's plane
+    let sides = |normal: Vec3<f32>, origin: Vec3<f32>, corners: [Vec3<f32>;3]| corners.map(|corner| normal.dot(corner - origin));
+    let all_one_side = |distances: [f32;3], normal: Vec3<f32>| {
+        let limit = tolerance * normal.magnitude();
+        distances.iter().all(|&d| d > limit) || distances.iter().all(|&d| d < -limit)
+    };
+    let distances_b = sides(normal_a, a[0], b);
+    if all_one_side(distances_b, normal_a) || all_one_side(sides(normal_b, b[0], a), normal_b) {
+        return None;
+    }
+
+    // Parallel and in the same plane, since the check above let it through
+    let parallel_limit = normal_a.magnitude() * normal_b.magnitude() * 1e-6;
+    if normal_a.cross(normal_b).magnitude_squared() <= parallel_limit * parallel_limit {
+        return coplanar_overlap(a, b, tolerance).then_some(None);
+    }
+
+    // The ends of the crossing segment always lie on an edge of one triangle or the other
+    let mut points: Vec<Vec3<f32>> = Vec::with_capacity(6);
+    for (edges, other) in [(a, b), (b, a)] {
+        for k in 0..3 {
+            let (p, q) = (edges[k], edges[(k + 1) % 3]);
+            if let Some((t, _, _)) = ray_triangle(p, q - p, other) {
+                if t <= 1.0 {
+                    points.push(p + (q - p) * t);
+                }
+            }
+        }
+    }
+    let first = *points.first()?;
+    let last = points.iter().copied().fold(first, |farthest, point| if point.distance_squared(first) > farthest.distance_squared(first) { point } else { farthest });
+    Some(Some([first, last]))
+}
+
+impl<'a> PlacedMesh<'a> {
+    // Walks both BVHs together, calling on_pair with every pair of touching triangles until it returns false
+    fn for_each_intersection(&self, other: &PlacedMesh, mut on_pair: impl FnMut(u32, u32, Option<[Vec3<f32>;2]>) -> bool) -> Result<(),StormworksParserError> {
+        // Everything is tested in this mesh's own space, a singular transform would turn it all into NaN and silently miss every hit
+        let determinant = self.transform.determinant();
+        let to_self = self.transform.inverted();
+        if determinant == 0.0 || !to_self.into_col_array().iter().all(|x| x.is_finite()) {
+            return Err(SingularTransform { determinant }.into());
+        }
+        if self.bvh.nodes.is_empty() || other.bvh.nodes.is_empty() {
+            return Ok(());
+        }
+        let other_to_self = to_self * other.transform;
+
+        let mut stack = Vec::with_capacity(64);
+        stack.push((0u32, 0u32));
+        while let Some((index_a, index_b)) = stack.pop() {
+            let (node_a, node_b) = (self.bvh.nodes[index_a as usize], other.bvh.nodes[index_b as usize]);
+            let aabb_b = transformed_aabb(&node_b.aabb, other_to_self);
+            if !node_a.aabb.intersects(&aabb_b) {
+                continue;
+            }
+
+            match (node_a.count > 0, node_b.count > 0) {
+                (true, true) => {
+                    for &triangle_a in &self.bvh.triangles[node_a.start as usize..(node_a.start + node_a.count) as usize] {
+                        let corners_a = triangle_positions(self.mesh, triangle_a);
+                        for &triangle_b in &other.bvh.triangles[node_b.start as usize..(node_b.start + node_b.count) as usize] {
+                            let corners_b = triangle_positions(other.mesh, triangle_b).map(|corner| other_to_self.mul_point(corner));
+                            if let Some(segment) = triangle_intersection(corners_a, corners_b) {
+                                let segment = segment.map(|ends| ends.map(|end| self.transform.mul_point(end)));
+                                if !on_pair(triangle_a, triangle_b, segment) {
+                                    return Ok(());
+                                }
+                            }
+                        }
+                    }
+                }
+                // Split the bigger box, or whichever one still can be
+                (false, true) => stack.extend([(index_a + 1, index_b), (node_a.start, index_b)]),
+                (true, false) => stack.extend([(index_a, index_b + 1), (index_a, node_b.start)]),
+                (false, false) => {
+                    if surface_area(&node_a.aabb) >= surface_area(&aabb_b) {
+                        stack.extend([(index_a + 1, index_b), (node_a.start, index_b)]);
+                    } else {
+                        stack.extend([(index_a, index_b + 1), (index_a, node_b.start)]);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Whether the surfaces touch anywhere. One mesh entirely inside the other doesn't count, MeshBvh::contains can check for that.
+    pub fn intersects(&self, other: &PlacedMesh) -> Result<bool,StormworksParserError> {
+        let mut found = false;
+        self.for_each_intersection(other, |_, _, _| {
+            found = true;
+            false
+        })?;
+        Ok(found)
+    }
+
+    // Every pair of touching triangles, a from this mesh and b from the other
+    pub fn intersections(&self, other: &PlacedMesh) -> Result<Vec<TrianglePair>,StormworksParserError> {
+        let mut pairs = Vec::new();
+        self.for_each_intersection(other, |a, b, segment| {
+            pairs.push(TrianglePair { a, b, segment });
+            true
+        })?;
+        Ok(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, MeshAnchor, PrimitiveStyle};
+    use vek::quaternion::repr_c::Quaternion;
+
+    fn cube(size_blocks: u32) -> (StormworksMesh, MeshBvh) {
+        let mut mesh = primitive_box(Vec3::broadcast(size_blocks), PrimitiveStyle::default()).unwrap();
+        mesh.center_on(MeshAnchor::BoundsCenter);
+        let bvh = mesh.build_bvh();
+        (mesh, bvh)
+    }
+
+    // Every triangle pair tested directly, as (a, b) sorted
+    fn brute_force(a: &PlacedMesh, b: &PlacedMesh) -> Vec<(u32, u32)> {
+        let world = |placed: &PlacedMesh, triangle: u32| triangle_positions(placed.mesh, triangle).map(|corner| placed.transform.mul_point(corner));
+        let mut pairs = Vec::new();
+        for i in 0..a.mesh.indices.len() as u32 / 3 {
+            for j in 0..b.mesh.indices.len() as u32 / 3 {
+                if triangle_intersection(world(a, i), world(b, j)).is_some() {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn sorted(pairs: &[TrianglePair]) -> Vec<(u32, u32)> {
+        let mut pairs: Vec<(u32, u32)> = pairs.iter().map(|pair| (pair.a, pair.b)).collect();
+        pairs.sort_unstable();
+        pairs
+    }
+
+    #[test]
+    fn offset_cubes_cross() {
+        let (mesh, bvh) = cube(4);
+        let a = PlacedMesh::new(&mesh, &bvh, Mat4::identity());
+        let b = PlacedMesh::new(&mesh, &bvh, Mat4::<f32>::translation_3d(Vec3::new(0.5, 0.3, 0.2)) * Mat4::from(Quaternion::rotation_y(0.3)));
+
+        assert!(a.intersects(&b).unwrap() && b.intersects(&a).unwrap());
+        let pairs = a.intersections(&b).unwrap();
+        assert_eq!(sorted(&pairs), brute_force(&a, &b));
+
+        // Crossing segments lie on both surfaces
+        let on_surface = |placed: &PlacedMesh, point: Vec3<f32>| {
+            let local = placed.transform.inverted().mul_point(point);
+            placed.bvh.distance(placed.mesh, local) < 1e-4
+        };
+        assert!(pairs.iter().all(|pair| pair.segment.is_some()));
+        for segment in pairs.iter().filter_map(|pair| pair.segment) {
+            assert!(segment.iter().all(|&point| on_surface(&a, point) && on_surface(&b, point)), "{segment:?}");
+        }
+    }
+
+    #[test]
+    fn separated_and_nested_cubes_dont_touch() {
+        let (mesh, bvh) = cube(4);
+        let a = PlacedMesh::new(&mesh, &bvh, Mat4::identity());
+        let b = PlacedMesh::new(&mesh, &bvh, Mat4::translation_3d(Vec3::new(1.2, 0.0, 0.0)));
+        assert!(!a.intersects(&b).unwrap());
+        assert!(a.intersections(&b).unwrap().is_empty());
+
+        // One inside the other has no touching surfaces
+        let (small, small_bvh) = cube(2);
+        let inside = PlacedMesh::new(&small, &small_bvh, Mat4::from(Quaternion::rotation_z(0.7)));
+        assert!(!a.intersects(&inside).unwrap());
+    }
+
+    #[test]
+    fn touching_faces_are_coplanar() {
+        let (mesh, bvh) = cube(4);
+        let a = PlacedMesh::new(&mesh, &bvh, Mat4::identity());
+        let b = PlacedMesh::new(&mesh, &bvh, Mat4::translation_3d(Vec3::new(1.0, 0.25, 0.0)));
+
+        let pairs = a.intersections(&b).unwrap();
+        assert_eq!(sorted(&pairs), brute_force(&a, &b));
+        assert!(pairs.iter().any(|pair| pair.segment.is_none()));
+    }
+
+    #[test]
+    fn singular_transforms_fail_instead_of_missing() {
+        let (mesh, bvh) = cube(4);
+        let a = PlacedMesh::new(&mesh, &bvh, Mat4::identity());
+        // Squashed flat onto the yz plane, right through the middle of a
+        let flat = PlacedMesh::new(&mesh, &bvh, Mat4::scaling_3d(Vec3::new(0.0, 1.0, 1.0)));
+
+        assert!(flat.intersects(&a).is_err());
+        assert!(flat.intersections(&a).is_err());
+        // Only the mesh queried from has to be invertible
+        assert!(a.intersects(&flat).unwrap());
+    }
+}
@@ -0,0 +1,220 @@
+use alloc::vec::Vec;
+
+use vek::{vec::repr_c::vec2::Vec2, Rgba};
+
+use crate::{normals::angle_between, prelude::*, StormworksMesh, StormworksMeshVertexRecord, StormworksParserError, StormworksSubMesh};
+
+const QUANTIZED_MAX: f32 = u16::MAX as f32;
+
+// 14 bytes per vertex instead of 28, and half size indices.
+// Positions are 16 bit fractions of the mesh bounds, normals are octahedral encoded into two 16 bit values.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompactMesh {
+    pub header0: u16,
+    pub header1: u16,
+    pub header3: u16,
+    pub header4: u16,
+    pub bounds_min: Vec3<f32>,
+    pub bounds_max: Vec3<f32>,
+    pub positions: Vec<[u16;3]>,
+    pub normals: Vec<[u16;2]>,
+    pub colors: Vec<Rgba<u8>>,
+    pub indices: Vec<u16>,
+    pub sub_meshes: Vec<StormworksSubMesh>,
+}
+
+// Largest differences measured between the original mesh and what the CompactMesh decodes back to
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct QuantizationReport {
+    // Distance in mesh units
+    pub max_position_error: f32,
+    // Angle in radians, zero length normals aren't counted
+    pub max_normal_error: f32,
+}
+
+fn sign_not_zero(v: f32) -> f32 {
+    if v >= 0.0 { 1.0 } else { -1.0 }
+}
+
+fn quantize_unit(v: f32) -> u16 {
+    ((v * 0.5 + 0.5) * QUANTIZED_MAX).round().clamp(0.0, QUANTIZED_MAX) as u16
+}
+
+fn dequantize_unit(q: u16) -> f32 {
+    q as f32 / QUANTIZED_MAX * 2.0 - 1.0
+}
+
+pub(crate) fn octahedral_encode(normal: Vec3<f32>) -> [u16;2] {
+    let sum = normal.x.abs() + normal.y.abs() + normal.z.abs();
+    let n = if sum > 0.0 { normal / sum } else { Vec3::new(0.0, 0.0, 1.0) };
+    let folded = if n.z >= 0.0 {
+        Vec2::new(n.x, n.y)
+    } else {
+        Vec2::new((1.0 - n.y.abs()) * sign_not_zero(n.x), (1.0 - n.x.abs()) * sign_not_zero(n.y))
+    };
+    [quantize_unit(folded.x), quantize_unit(folded.y)]
+}
+
+pub(crate) fn octahedral_decode(encoded: [u16;2]) -> Vec3<f32> {
+    let (x, y) = (dequantize_unit(encoded[0]), dequantize_unit(encoded[1]));
+    let z = 1.0 - x.abs() - y.abs();
+    let n = if z >= 0.0 {
+        Vec3::new(x, y, z)
+    } else {
+        Vec3::new((1.0 - y.abs()) * sign_not_zero(x), (1.0 - x.abs()) * sign_not_zero(y), z)
+    };
+    n.normalized()
+}
+
+impl CompactMesh {
+    // Fails with the same errors as StormworksMesh::validate, which also guarantees every index fits in a u16
+    pub fn from_mesh(mesh: &StormworksMesh) -> Result<(CompactMesh, QuantizationReport), StormworksParserError> {
+        mesh.validate()?;
+
+        let (bounds_min, bounds_max) = match mesh.vertices.first() {
+            Some(first) => mesh.vertices.iter().fold((first.position, first.position), |(min, max), vertex| {
+                (Vec3::partial_min(min, vertex.position), Vec3::partial_max(max, vertex.position))
+            }),
+            None => (Vec3::zero(), Vec3::zero()),
+        };
+        let extent = bounds_max - bounds_min;
+        // Flat axes quantize to 0 instead of dividing by zero
+        let scale = extent.map(|e| if e > 0.0 { QUANTIZED_MAX / e } else { 0.0 });
+
+        let compact = CompactMesh {
+            header0: mesh.header0,
+            header1: mesh.header1,
+            header3: mesh.header3,
+            header4: mesh.header4,
+            bounds_min,
+            bounds_max,
+            positions: mesh
+                .vertices
+                .iter()
+                .map(|vertex| {
+                    ((vertex.position - bounds_min) * scale)
+                        .map(|q| q.round().clamp(0.0, QUANTIZED_MAX) as u16)
+                        .into_array()
+                })
+                .collect(),
+            normals: mesh.vertices.iter().map(|vertex| octahedral_encode(vertex.normal)).collect(),
+            colors: mesh.vertices.iter().map(|vertex| vertex.color).collect(),
+            indices: mesh.indices.iter().map(|&index| index as u16).collect(),
+            sub_meshes: mesh.sub_meshes.clone(),
+        };
+
+        let mut report = QuantizationReport::default();
+        for (i, vertex) in mesh.vertices.iter().enumerate() {
+            let position_error = compact.position(i).distance(vertex.position);
+            report.max_position_error = report.max_position_error.max(position_error);
+
+            if vertex.normal.magnitude_squared() > 0.0 {
+                report.max_normal_error = report.max_normal_error.max(angle_between(compact.normal(i), vertex.normal.normalized()));
+            }
+        }
+
+        Ok((compact, report))
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    // Worst case position error from quantization alone, half a step along each axis
+    pub fn position_error_bound(&self) -> f32 {
+        ((self.bounds_max - self.bounds_min) / QUANTIZED_MAX * 0.5).magnitude()
+    }
+
+    pub fn position(&self, i: usize) -> Vec3<f32> {
+        let extent = self.bounds_max - self.bounds_min;
+        self.bounds_min + Vec3::from(self.positions[i]).map(|q: u16| q as f32 / QUANTIZED_MAX) * extent
+    }
+
+    pub fn normal(&self, i: usize) -> Vec3<f32> {
+        octahedral_decode(self.normals[i])
+    }
+
+    pub fn to_mesh(&self) -> StormworksMesh {
+        StormworksMesh {
+            header0: self.header0,
+            header1: self.header1,
+            header3: self.header3,
+            header4: self.header4,
+            vertex_count: self.positions.len() as u32,
+            vertices: (0..self.positions.len())
+                .map(|i| StormworksMeshVertexRecord {
+                    position: self.position(i),
+                    color: self.colors[i],
+                    normal: self.normal(i),
+                })
+                .collect(),
+            index_count: self.indices.len() as u32,
+            indices: self.indices.iter().map(|&index| index as u32).collect(),
+            sub_mesh_count: self.sub_meshes.len() as u32,
+            sub_meshes: self.sub_meshes.clone(),
+        }
+    }
+}
+
+impl StormworksMesh {
+    pub fn to_compact(&self) -> Result<(CompactMesh, QuantizationReport), StormworksParserError> {
+        CompactMesh::from_mesh(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, primitive_sphere, PrimitiveStyle};
+
+    #[test]
+    fn octahedral_round_trip() {
+        let mut worst: f32 = 0.0;
+        for i in 0..200 {
+            let i = i as f32;
+            let normal = Vec3::new((i * 0.37).sin(), (i * 0.71).cos(), (i * 1.3).sin() - 0.2).normalized();
+            worst = worst.max(angle_between(octahedral_decode(octahedral_encode(normal)), normal));
+        }
+        for axis in [Vec3::unit_x(), -Vec3::unit_y(), Vec3::unit_z(), -Vec3::unit_z()] {
+            worst = worst.max(angle_between(octahedral_decode(octahedral_encode(axis)), axis));
+        }
+        // Two 16 bit values are good to a few hundredths of a degree
+        assert!(worst < 1e-4, "{worst}");
+    }
+
+    #[test]
+    fn round_trip_stays_within_the_reported_error() {
+        let mesh = primitive_sphere(3.0, 24, 12, PrimitiveStyle::default()).unwrap();
+        let (compact, report) = mesh.to_compact().unwrap();
+        assert_eq!(compact.vertex_count(), mesh.vertices.len());
+        assert!(report.max_position_error <= compact.position_error_bound());
+        assert!(report.max_normal_error < 1e-4);
+
+        let decoded = compact.to_mesh();
+        decoded.validate().unwrap();
+        assert_eq!(decoded.indices, mesh.indices);
+        assert_eq!(decoded.sub_meshes, mesh.sub_meshes);
+        for (decoded, original) in decoded.vertices.iter().zip(&mesh.vertices) {
+            assert!(decoded.position.distance(original.position) <= report.max_position_error);
+            assert!(angle_between(decoded.normal, original.normal) <= report.max_normal_error + 1e-6);
+            assert_eq!(decoded.color, original.color);
+        }
+    }
+
+    #[test]
+    fn flat_and_empty_meshes() {
+        // Every vertex of the bottom face has the same y, which has no extent to quantize
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        let bottom = mesh.vertices.iter().map(|vertex| vertex.position.y).fold(f32::INFINITY, f32::min);
+        mesh.vertices.iter_mut().for_each(|vertex| vertex.position.y = bottom);
+        let (compact, report) = mesh.to_compact().unwrap();
+        assert!(compact.position(0).y == bottom && report.max_position_error <= compact.position_error_bound());
+
+        let (compact, report) = StormworksMesh::default().to_compact().unwrap();
+        assert_eq!(compact.to_mesh(), StormworksMesh::default());
+        assert_eq!(report, QuantizationReport::default());
+
+        mesh.indices[0] = mesh.vertex_count;
+        assert!(mesh.to_compact().is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use alloc::{collections::VecDeque, string::String, vec::Vec};
+
+use vek::vec::repr_c::vec3::Vec3;
+
+use crate::{check_sub_mesh_range, decode_vertex_record, detect_format_version, shader_from_u16, DecoderAlreadyFailed, IndexIndexOutOfBounds, MeshFormatVersion, StormworksMesh, StormworksMeshVertexRecord, StormworksParseOptions, StormworksParserError, StormworksSubMesh, TooBigNameLength, UnexpectedEndOfData};
+
+// Bytes of a sub mesh up to and including its name length, everything after that depends on the name length
+const SUB_MESH_FIXED_BYTES: usize = 4+4+2+2+4*3+4*3+2+2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Progress {
+    NeedMoreData,
+    Done,
+}
+
+// What MeshDecoder::feed produced, in file order
+#[derive(Clone, Debug, PartialEq)]
+pub enum MeshDecoderEvent {
+    Header { headers: [u16;4], version: MeshFormatVersion, vertex_count: u32 },
+    Vertex(StormworksMeshVertexRecord),
+    IndexCount(u32),
+    Index(u32),
+    SubMeshCount(u32),
+    SubMesh(StormworksSubMesh),
+}
+
+enum DecoderState {
+    Magic,
+    Headers,
+    Vertices { version: MeshFormatVersion, remaining: u32 },
+    IndexCount,
+    Indices { remaining: u32 },
+    SubMeshCount,
+    SubMeshFixed { remaining: u32 },
+    // The first part of the sub mesh is already read into `partial`, the name and header8 are left
+    SubMeshTail { remaining: u32, partial: StormworksSubMesh },
+    Done,
+    Failed,
+}
+
+// Push based parser for when the bytes of a .mesh arrive in pieces, like over a pipe.
+// Chunks can be split anywhere, only the item currently being read is buffered, and nothing blocks or needs Seek.
+// Finished items are queued as MeshDecoderEvents, take them with next_event.
+pub struct MeshDecoder {
+    options: StormworksParseOptions,
+    state: DecoderState,
+    pending: Vec<u8>,
+    events: VecDeque<MeshDecoderEvent>,
+    vertex_count: u32,
+    index_count: u32,
+    indices_read: u32,
+    sub_mesh_count: u32,
+}
+impl Default for MeshDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MeshDecoder {
+    pub fn new() -> Self {
+        Self::with_options(StormworksParseOptions::default())
+    }
+    pub fn with_options(options: StormworksParseOptions) -> Self {
+        MeshDecoder {
+            options,
+            state: DecoderState::Magic,
+            pending: Vec::new(),
+            events: VecDeque::new(),
+            vertex_count: 0,
+            index_count: 0,
+            indices_read: 0,
+            sub_mesh_count: 0,
+        }
+    }
+
+    pub fn next_event(&mut self) -> Option<MeshDecoderEvent> {
+        self.events.pop_front()
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, DecoderState::Done)
+    }
+
+    // Bytes after the end of the mesh are ignored. Once feed has failed, every later call fails too.
+    pub fn feed(&mut self, mut data: &[u8]) -> Result<Progress,StormworksParserError> {
+        loop {
+            let needed = match &self.state {
+                DecoderState::Done => return Ok(Progress::Done),
+                DecoderState::Failed => return Err(DecoderAlreadyFailed.into()),
+                DecoderState::Magic => 4,
+                DecoderState::Headers => 2*5,
+                DecoderState::Vertices { version, .. } => version.bytes_per_vertex(),
+                DecoderState::IndexCount => 4,
+                DecoderState::Indices { .. } => 2,
+                DecoderState::SubMeshCount => 2,
+                DecoderState::SubMeshFixed { .. } => SUB_MESH_FIXED_BYTES,
+                DecoderState::SubMeshTail { partial, .. } => partial.name_length_bytes as usize + 4*3,
+            };
+
+            let result = if self.pending.is_empty() && data.len() >= needed {
+                // Whole item is in this chunk, no need to copy it
+                let (item, rest) = data.split_at(needed);
+                data = rest;
+                self.process(item)
+            } else {
+                let take = (needed - self.pending.len()).min(data.len());
+                self.pending.extend_from_slice(&data[..take]);
+                data = &data[take..];
+                if self.pending.len() < needed {
+                    return Ok(Progress::NeedMoreData);
+                }
+                let item = core::mem::take(&mut self.pending);
+                let result = self.process(&item);
+                self.pending = item;
+                self.pending.clear();
+                result
+            };
+
+            if let Err(err) = result {
+                self.state = DecoderState::Failed;
+                return Err(err);
+            }
+        }
+    }
+
+    // `item` is exactly the bytes the current state asked for
+    fn process(&mut self, item: &[u8]) -> Result<(),StormworksParserError> {
+        let u16_at = |offset: usize| u16::from_le_bytes([item[offset], item[offset + 1]]);
+        let u32_at = |offset: usize| u32::from_le_bytes([item[offset], item[offset + 1], item[offset + 2], item[offset + 3]]);
+        let f32_at = |offset: usize| f32::from_bits(u32_at(offset));
+        let vec3_at = |offset: usize| Vec3::new(f32_at(offset), f32_at(offset + 4), f32_at(offset + 8));
+
+        self.state = match core::mem::replace(&mut self.state, DecoderState::Failed) {
+            DecoderState::Magic => {
+                if item != b"mesh" {
+                    return Err(StormworksParserError::NotMesh);
+                }
+                DecoderState::Headers
+            }
+            DecoderState::Headers => {
+                let headers = [u16_at(0), u16_at(2), u16_at(6), u16_at(8)];
+                let version = detect_format_version(headers, &self.options)?;
+                self.vertex_count = u16_at(4) as u32;
+                self.events.push_back(MeshDecoderEvent::Header { headers, version, vertex_count: self.vertex_count });
+                self.after_vertices(version, self.vertex_count)
+            }
+            DecoderState::Vertices { version, remaining } => {
+                self.events.push_back(MeshDecoderEvent::Vertex(decode_vertex_record(item, version)?));
+                self.after_vertices(version, remaining - 1)
+            }
+            DecoderState::IndexCount => {
+                self.index_count = u32_at(0);
+                self.events.push_back(MeshDecoderEvent::IndexCount(self.index_count));
+                self.after_indices(self.index_count)
+            }
+            DecoderState::Indices { remaining } => {
+                let index = u16_at(0) as u32;
+                if index >= self.vertex_count {
+                    return Err(IndexIndexOutOfBounds { index: self.indices_read, vertex_count: self.vertex_count }.into());
+                }
+                self.indices_read += 1;
+                self.events.push_back(MeshDecoderEvent::Index(index));
+                self.after_indices(remaining - 1)
+            }
+            DecoderState::SubMeshCount => {
+                self.sub_mesh_count = u16_at(0) as u32;
+                self.events.push_back(MeshDecoderEvent::SubMeshCount(self.sub_mesh_count));
+                self.after_sub_meshes(self.sub_mesh_count)
+            }
+            DecoderState::SubMeshFixed { remaining } => {
+                let name_length_bytes = u16_at(38);
+                if name_length_bytes > 1_000 {
+                    return Err(TooBigNameLength.into());
+                }
+                let partial = StormworksSubMesh {
+                    index_buffer_start: u32_at(0),
+                    index_buffer_length: u32_at(4),
+                    header2: u16_at(8),
+                    shader_id: shader_from_u16(u16_at(10), &self.options)?,
+                    bounds_min: vec3_at(12),
+                    bounds_max: vec3_at(24),
+                    header6: u16_at(36),
+                    name_length_bytes,
+                    name: String::new(),
+                    header8: Vec3::zero(),
+                };
+                DecoderState::SubMeshTail { remaining, partial }
+            }
+            DecoderState::SubMeshTail { remaining, mut partial } => {
+                let name_length = partial.name_length_bytes as usize;
+                partial.name = String::from_utf8(item[..name_length].to_vec())?;
+                partial.header8 = vec3_at(name_length);
+
+                // Same checks as fill_sub_meshes
+                let submesh_id = self.sub_mesh_count - remaining;
+                check_sub_mesh_range(submesh_id, &partial, self.index_count)?;
+
+                self.events.push_back(MeshDecoderEvent::SubMesh(partial));
+                self.after_sub_meshes(remaining - 1)
+            }
+            DecoderState::Done | DecoderState::Failed => unreachable!("feed doesn't process anything once finished"),
+        };
+        Ok(())
+    }
+
+    // Empty blocks are skipped straight over, since there are no bytes to wait for
+    fn after_vertices(&self, version: MeshFormatVersion, remaining: u32) -> DecoderState {
+        if remaining == 0 { DecoderState::IndexCount } else { DecoderState::Vertices { version, remaining } }
+    }
+    fn after_indices(&self, remaining: u32) -> DecoderState {
+        if remaining == 0 { DecoderState::SubMeshCount } else { DecoderState::Indices { remaining } }
+    }
+    fn after_sub_meshes(&self, remaining: u32) -> DecoderState {
+        if remaining == 0 { DecoderState::Done } else { DecoderState::SubMeshFixed { remaining } }
+    }
+}
+
+impl StormworksMesh {
+    // Builds up a mesh from MeshDecoder events, starting from StormworksMesh::default()
+    pub fn apply_decoder_event(&mut self, event: MeshDecoderEvent) {
+        match event {
+            MeshDecoderEvent::Header { headers: [header0, header1, header3, header4], vertex_count, .. } => {
+                (self.header0, self.header1, self.header3, self.header4) = (header0, header1, header3, header4);
+                self.vertex_count = vertex_count;
+                self.vertices.reserve(vertex_count as usize);
+            }
+            MeshDecoderEvent::Vertex(vertex) => self.vertices.push(vertex),
+            MeshDecoderEvent::IndexCount(index_count) => self.index_count = index_count,
+            MeshDecoderEvent::Index(index) => self.indices.push(index),
+            MeshDecoderEvent::SubMeshCount(sub_mesh_count) => {
+                self.sub_mesh_count = sub_mesh_count;
+                self.sub_meshes.reserve(sub_mesh_count as usize);
+            }
+            MeshDecoderEvent::SubMesh(sub_mesh) => self.sub_meshes.push(sub_mesh),
+        }
+    }
+}
+
+// Parses a mesh that is already fully in memory, the way to load meshes without std::io
+pub fn parse_stormworks_mesh(bytes: &[u8]) -> Result<StormworksMesh,StormworksParserError> {
+    parse_stormworks_mesh_with_options(bytes, &StormworksParseOptions::default())
+}
+pub fn parse_stormworks_mesh_with_options(bytes: &[u8], options: &StormworksParseOptions) -> Result<StormworksMesh,StormworksParserError> {
+    // Fed in pieces so the event queue never holds more than a chunk's worth
+    const CHUNK_BYTES: usize = 64 * 1024;
+
+    let mut decoder = MeshDecoder::with_options(options.clone());
+    let mut mesh = StormworksMesh::default();
+    let mut progress = Progress::NeedMoreData;
+    for chunk in bytes.chunks(CHUNK_BYTES) {
+        progress = decoder.feed(chunk)?;
+        while let Some(event) = decoder.next_event() {
+            mesh.apply_decoder_event(event);
+        }
+        if progress == Progress::Done {
+            break;
+        }
+    }
+
+    match progress {
+        Progress::Done => Ok(mesh),
+        Progress::NeedMoreData => Err(UnexpectedEndOfData.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, primitive_sphere, PrimitiveStyle, StormworksShaderType};
+    use vek::mat::repr_c::mat4::Mat4;
+
+    // A box and a sphere with different shaders, so there are two named sub meshes
+    fn fixture() -> Vec<u8> {
+        let emissive = PrimitiveStyle { shader: StormworksShaderType::Emissive, ..Default::default() };
+        let inputs = [
+            (primitive_box(Vec3::new(2, 3, 4), PrimitiveStyle::default()).unwrap(), Mat4::identity()),
+            (primitive_sphere(0.5, 8, 4, emissive).unwrap(), Mat4::translation_3d(Vec3::new(2.0, 0.0, 0.0))),
+        ];
+        let mesh = StormworksMesh::merge(&inputs).unwrap().remove(0);
+        assert_eq!(mesh.sub_meshes.len(), 2);
+
+        let mut bytes = b"mesh".to_vec();
+        let u16s = |bytes: &mut Vec<u8>, values: &[u16]| values.iter().for_each(|value| bytes.extend_from_slice(&value.to_le_bytes()));
+        let vec3 = |bytes: &mut Vec<u8>, v: Vec3<f32>| [v.x, v.y, v.z].iter().for_each(|c| bytes.extend_from_slice(&c.to_le_bytes()));
+        u16s(&mut bytes, &[mesh.header0, mesh.header1, mesh.vertex_count as u16, mesh.header3, mesh.header4]);
+        for vertex in &mesh.vertices {
+            vec3(&mut bytes, vertex.position);
+            bytes.extend_from_slice(&[vertex.color.r, vertex.color.g, vertex.color.b, vertex.color.a]);
+            vec3(&mut bytes, vertex.normal);
+        }
+        bytes.extend_from_slice(&mesh.index_count.to_le_bytes());
+        for &index in &mesh.indices {
+            u16s(&mut bytes, &[index as u16]);
+        }
+        u16s(&mut bytes, &[mesh.sub_mesh_count as u16]);
+        for sub_mesh in &mesh.sub_meshes {
+            bytes.extend_from_slice(&sub_mesh.index_buffer_start.to_le_bytes());
+            bytes.extend_from_slice(&sub_mesh.index_buffer_length.to_le_bytes());
+            u16s(&mut bytes, &[sub_mesh.header2, sub_mesh.shader_id.to_u16()]);
+            vec3(&mut bytes, sub_mesh.bounds_min);
+            vec3(&mut bytes, sub_mesh.bounds_max);
+            u16s(&mut bytes, &[sub_mesh.header6, sub_mesh.name_length_bytes]);
+            bytes.extend_from_slice(sub_mesh.name.as_bytes());
+            vec3(&mut bytes, sub_mesh.header8);
+        }
+        bytes
+    }
+
+    // Feeds the chunks in order and builds the mesh from the events
+    fn decode<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Result<StormworksMesh,StormworksParserError> {
+        let mut decoder = MeshDecoder::new();
+        let mut mesh = StormworksMesh::default();
+        for chunk in chunks {
+            let progress = decoder.feed(chunk)?;
+            while let Some(event) = decoder.next_event() {
+                mesh.apply_decoder_event(event);
+            }
+            if progress == Progress::Done {
+                return Ok(mesh);
+            }
+        }
+        Err(UnexpectedEndOfData.into())
+    }
+
+    #[test]
+    fn one_byte_at_a_time() {
+        let bytes = fixture();
+        let expected = parse_stormworks_mesh(&bytes).unwrap();
+        assert_eq!(expected.sub_meshes[1].shader_id, StormworksShaderType::Emissive);
+        assert_eq!(decode(bytes.chunks(1)).unwrap(), expected);
+    }
+
+    #[test]
+    fn random_split_points() {
+        let bytes = fixture();
+        let expected = parse_stormworks_mesh(&bytes).unwrap();
+        // Small linear congruential generator, so failures can be reproduced
+        let mut state = 0x2545_f491_u32;
+        for _ in 0..50 {
+            let mut chunks = Vec::new();
+            let mut rest = &bytes[..];
+            while !rest.is_empty() {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                let (chunk, tail) = rest.split_at((state >> 16) as usize % 97 % rest.len() + 1);
+                chunks.push(chunk);
+                rest = tail;
+            }
+            assert_eq!(decode(chunks).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_ignored() {
+        let mut bytes = fixture();
+        let expected = parse_stormworks_mesh(&bytes).unwrap();
+        bytes.extend_from_slice(b"trailing");
+        assert_eq!(decode([&bytes[..]]).unwrap(), expected);
+    }
+
+    #[test]
+    fn truncated_stream_fails() {
+        let bytes = fixture();
+        for length in [0, 3, 20, bytes.len() / 2, bytes.len() - 1] {
+            let mut decoder = MeshDecoder::new();
+            assert_eq!(decoder.feed(&bytes[..length]).unwrap(), Progress::NeedMoreData);
+            assert!(!decoder.is_done());
+            assert!(matches!(parse_stormworks_mesh(&bytes[..length]), Err(StormworksParserError::CorruptFile(_))));
+        }
+
+        // Once failed, it stays failed
+        let mut decoder = MeshDecoder::new();
+        assert!(matches!(decoder.feed(b"nope"), Err(StormworksParserError::NotMesh)));
+        assert!(decoder.feed(&bytes).is_err());
+    }
+}
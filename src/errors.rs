@@ -1,11 +1,16 @@
-use std::{array, fmt, io, string};
+use alloc::{boxed::Box, string::{self, String}};
+use core::{array, fmt};
+#[cfg(feature = "std")]
+use std::io;
 
-// Outward-facing error for the user of this lib. Groups all known potential failures of this library into the only two cases the user needs to care about.
+// Outward-facing error for the user of this lib. Groups all known potential failures of this library into the three cases the user needs to care about.
 pub enum StormworksParserError {
 	NotMesh,
+	// header0, header1, header3 and header4 don't match any MeshFormatVersion
+	UnsupportedVersion { headers: [u16;4] },
 	CorruptFile(Box<dyn SpecificError>)
 }
-impl std::error::Error for StormworksParserError {}
+impl core::error::Error for StormworksParserError {}
 
 
 // Error types unique to this lib
@@ -13,16 +18,31 @@ pub(crate) struct SubMeshIndexOutOfBounds {pub submesh_id: u32, pub index: u32,
 pub(crate) struct IndexIndexOutOfBounds {pub index: u32, pub vertex_count: u32}
 pub(crate) struct TooBigNameLength;//previously known as larderous
 pub(crate) struct InvalidStormworksShaderType(pub u16);
+pub(crate) struct InvalidMeshText {pub line: usize, pub reason: String}
+pub(crate) struct MismatchedCount {pub field: &'static str, pub count: u32, pub actual: usize}
+pub(crate) struct ExceedsFormatLimit {pub field: &'static str, pub count: u32, pub limit: u32}
+pub(crate) struct MeshSizeMismatch {pub expected_at_least: u64, pub file_length: u64}
+pub(crate) struct DecoderAlreadyFailed;
+pub(crate) struct UnexpectedEndOfData;
+pub(crate) struct SingularTransform {pub determinant: f32}
 
 // SpecificError serves to group all potential errors this function can fail with, and no more.
 pub(crate) trait SpecificError: fmt::Display+fmt::Debug + Send + Sync {}
 impl SpecificError for string::FromUtf8Error {}
+#[cfg(feature = "std")]
 impl SpecificError for io::Error {}
 impl SpecificError for array::TryFromSliceError {}
 impl SpecificError for SubMeshIndexOutOfBounds {}
 impl SpecificError for IndexIndexOutOfBounds {}
 impl SpecificError for TooBigNameLength {}
 impl SpecificError for InvalidStormworksShaderType {}
+impl SpecificError for InvalidMeshText {}
+impl SpecificError for MismatchedCount {}
+impl SpecificError for ExceedsFormatLimit {}
+impl SpecificError for MeshSizeMismatch {}
+impl SpecificError for DecoderAlreadyFailed {}
+impl SpecificError for UnexpectedEndOfData {}
+impl SpecificError for SingularTransform {}
 
 // The actual error message for the error types that are unique to this lib
 impl fmt::Display for SubMeshIndexOutOfBounds {
@@ -45,6 +65,41 @@ impl fmt::Display for InvalidStormworksShaderType {
 	  write!(f, "Tried to make shader with type: {}", self.0)
   }
 }
+impl fmt::Display for InvalidMeshText {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "Mesh text is invalid on line {}: {}", self.line, self.reason)
+  }
+}
+impl fmt::Display for MismatchedCount {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "{} is {}, but there are {}", self.field, self.count, self.actual)
+  }
+}
+impl fmt::Display for ExceedsFormatLimit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "{} is {}, but a .mesh file can hold at most {}", self.field, self.count, self.limit)
+  }
+}
+impl fmt::Display for MeshSizeMismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "The counts in the file need at least {} bytes, but it is only {} bytes long", self.expected_at_least, self.file_length)
+  }
+}
+impl fmt::Display for DecoderAlreadyFailed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "MeshDecoder was fed more data after it already failed")
+  }
+}
+impl fmt::Display for UnexpectedEndOfData {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "The data ended before the mesh did")
+  }
+}
+impl fmt::Display for SingularTransform {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "A placement transform has determinant {}, so it can't be inverted", self.determinant)
+  }
+}
 // Copied for debug
 impl fmt::Debug for SubMeshIndexOutOfBounds {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -66,11 +121,47 @@ impl fmt::Debug for InvalidStormworksShaderType {
 	  write!(f, "Tried to make shader with type: {}", self.0)
   }
 }
+impl fmt::Debug for InvalidMeshText {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "Mesh text is invalid on line {}: {}", self.line, self.reason)
+  }
+}
+impl fmt::Debug for MismatchedCount {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "{} is {}, but there are {}", self.field, self.count, self.actual)
+  }
+}
+impl fmt::Debug for ExceedsFormatLimit {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "{} is {}, but a .mesh file can hold at most {}", self.field, self.count, self.limit)
+  }
+}
+impl fmt::Debug for MeshSizeMismatch {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "The counts in the file need at least {} bytes, but it is only {} bytes long", self.expected_at_least, self.file_length)
+  }
+}
+impl fmt::Debug for DecoderAlreadyFailed {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "MeshDecoder was fed more data after it already failed")
+  }
+}
+impl fmt::Debug for UnexpectedEndOfData {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "The data ended before the mesh did")
+  }
+}
+impl fmt::Debug for SingularTransform {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+	  write!(f, "A placement transform has determinant {}, so it can't be inverted", self.determinant)
+  }
+}
 
 impl fmt::Display for StormworksParserError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		 match self {
 			  StormworksParserError::NotMesh => write!(f, "File is not a .mesh"),
+			  StormworksParserError::UnsupportedVersion { headers } => write!(f, "File is a .mesh of a version this library can't read, headers: {:?}", headers),
 			  StormworksParserError::CorruptFile(err) => {
 					write!(f, "File doesn't represent a valid mesh - Did you try to parse a non-stormworks mesh, or is the file corrupted? Internal library error: {}", err)
 			  }
@@ -81,6 +172,7 @@ impl fmt::Debug for StormworksParserError {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		match self {
 			 StormworksParserError::NotMesh => write!(f, "File is not a .mesh"),
+			 StormworksParserError::UnsupportedVersion { headers } => write!(f, "File is a .mesh of a version this library can't read, headers: {:?}", headers),
 			 StormworksParserError::CorruptFile(err) => {
 				  write!(f, "File doesn't represent a valid mesh - Did you try to parse a non-stormworks mesh, or is the file corrupted? Internal library error: {}", err)
 			 }
@@ -94,6 +186,7 @@ impl From<string::FromUtf8Error> for Box<dyn SpecificError> {
         Box::new(err)
     }
 }
+#[cfg(feature = "std")]
 impl From<io::Error> for Box<dyn SpecificError> {
     fn from(err: io::Error) -> Self {
         Box::new(err)
@@ -124,6 +217,41 @@ impl From<InvalidStormworksShaderType> for Box<dyn SpecificError> {
         Box::new(err)
     }
 }
+impl From<InvalidMeshText> for Box<dyn SpecificError> {
+    fn from(err: InvalidMeshText) -> Self {
+        Box::new(err)
+    }
+}
+impl From<MismatchedCount> for Box<dyn SpecificError> {
+    fn from(err: MismatchedCount) -> Self {
+        Box::new(err)
+    }
+}
+impl From<ExceedsFormatLimit> for Box<dyn SpecificError> {
+    fn from(err: ExceedsFormatLimit) -> Self {
+        Box::new(err)
+    }
+}
+impl From<MeshSizeMismatch> for Box<dyn SpecificError> {
+    fn from(err: MeshSizeMismatch) -> Self {
+        Box::new(err)
+    }
+}
+impl From<DecoderAlreadyFailed> for Box<dyn SpecificError> {
+    fn from(err: DecoderAlreadyFailed) -> Self {
+        Box::new(err)
+    }
+}
+impl From<UnexpectedEndOfData> for Box<dyn SpecificError> {
+    fn from(err: UnexpectedEndOfData) -> Self {
+        Box::new(err)
+    }
+}
+impl From<SingularTransform> for Box<dyn SpecificError> {
+    fn from(err: SingularTransform) -> Self {
+        Box::new(err)
+    }
+}
 
 impl From<Box<dyn SpecificError>> for StormworksParserError {
 	fn from(value: Box<dyn SpecificError>) -> Self {
@@ -135,6 +263,7 @@ impl From<string::FromUtf8Error> for StormworksParserError {
 		StormworksParserError::CorruptFile(Box::new(err))
 	}
 }
+#[cfg(feature = "std")]
 impl From<io::Error> for StormworksParserError {
 	fn from(err: io::Error) -> Self {
 		StormworksParserError::CorruptFile(Box::new(err))
@@ -164,4 +293,39 @@ impl From<InvalidStormworksShaderType> for StormworksParserError {
 	fn from(err: InvalidStormworksShaderType) -> Self {
 		 StormworksParserError::CorruptFile(Box::new(err))
 	}
+}
+impl From<InvalidMeshText> for StormworksParserError {
+	fn from(err: InvalidMeshText) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<MismatchedCount> for StormworksParserError {
+	fn from(err: MismatchedCount) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<ExceedsFormatLimit> for StormworksParserError {
+	fn from(err: ExceedsFormatLimit) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<MeshSizeMismatch> for StormworksParserError {
+	fn from(err: MeshSizeMismatch) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<DecoderAlreadyFailed> for StormworksParserError {
+	fn from(err: DecoderAlreadyFailed) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<UnexpectedEndOfData> for StormworksParserError {
+	fn from(err: UnexpectedEndOfData) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
+}
+impl From<SingularTransform> for StormworksParserError {
+	fn from(err: SingularTransform) -> Self {
+		 StormworksParserError::CorruptFile(Box::new(err))
+	}
 }
\ No newline at end of file
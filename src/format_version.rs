@@ -0,0 +1,48 @@
+use crate::BYTES_PER_VERTEX;
+
+// The file stores four u16s around the vertex count: header0 and header1 before it, header3 and header4 after.
+// Every game mesh seen so far has them as 7, 1, 0x13, 0, and CodeLeopard's loader treats anything else as suspect.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MeshFormatVersion {
+    // 28 byte vertices: f32 position, u8 rgba color, f32 normal
+    V7,
+}
+impl MeshFormatVersion {
+    pub fn from_headers(headers: [u16;4]) -> Option<Self> {
+        match headers {
+            [7, 1, 0x13, 0] => Some(MeshFormatVersion::V7),
+            _ => None,
+        }
+    }
+
+    // header0, header1, header3 and header4, in that order, as a file of this version has them
+    pub fn headers(&self) -> [u16;4] {
+        match self {
+            MeshFormatVersion::V7 => [7, 1, 0x13, 0],
+        }
+    }
+
+    pub fn bytes_per_vertex(&self) -> usize {
+        match self {
+            MeshFormatVersion::V7 => BYTES_PER_VERTEX,
+        }
+    }
+
+    // Where each attribute starts inside a vertex record, for decoding attributes on their own
+    pub fn position_offset(&self) -> usize {
+        match self {
+            MeshFormatVersion::V7 => 0,
+        }
+    }
+    pub fn color_offset(&self) -> usize {
+        match self {
+            MeshFormatVersion::V7 => 12,
+        }
+    }
+    pub fn normal_offset(&self) -> usize {
+        match self {
+            MeshFormatVersion::V7 => 16,
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::io::Read;
+
+use vek::{vec::repr_c::vec3::Vec3, Rgba};
+
+use crate::{build_indices, build_sub_meshes, detect_format_version, read_u16_from, read_u32_from, MeshFormatVersion, StormworksParseOptions, StormworksParserError, StormworksShaderType, StormworksSubMesh};
+
+// Which sub meshes build_stormworks_geometry keeps
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum SubMeshFilter {
+    #[default]
+    All,
+    Name(String),
+    Shader(StormworksShaderType),
+}
+impl SubMeshFilter {
+    pub fn matches(&self, sub_mesh: &StormworksSubMesh) -> bool {
+        match self {
+            SubMeshFilter::All => true,
+            SubMeshFilter::Name(name) => sub_mesh.name == *name,
+            SubMeshFilter::Shader(shader) => sub_mesh.shader_id == *shader,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct StormworksDecodeOptions {
+    pub parse: StormworksParseOptions,
+    pub normals: bool,
+    pub colors: bool,
+    pub sub_meshes: SubMeshFilter,
+}
+impl Default for StormworksDecodeOptions {
+    fn default() -> Self {
+        StormworksDecodeOptions {
+            parse: StormworksParseOptions::default(),
+            normals: true,
+            colors: true,
+            sub_meshes: SubMeshFilter::All,
+        }
+    }
+}
+
+// A StormworksMesh with only the parts that were asked for, see StormworksDecodeOptions.
+// normals and colors are None when skipped, otherwise they line up with positions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StormworksGeometry {
+    pub version: MeshFormatVersion,
+    pub positions: Vec<Vec3<f32>>,
+    pub normals: Option<Vec<Vec3<f32>>>,
+    pub colors: Option<Vec<Rgba<u8>>>,
+    pub indices: Vec<u32>,
+    pub sub_meshes: Vec<StormworksSubMesh>,
+}
+
+fn decode_vec3(bytes: &[u8]) -> Vec3<f32> {
+    Vec3::new(
+        f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+        f32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]),
+        f32::from_le_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+    )
+}
+
+// Sub meshes come after the vertices in the file, so which vertices are needed isn't known until the end.
+// The vertex block is kept as raw bytes until then, and only the wanted attributes of the wanted vertices get decoded.
+// With a sub mesh filter, the kept sub meshes' index ranges are packed together and only the vertices they use are kept.
+pub fn build_stormworks_geometry(mut mesh_stream: impl Read, options: &StormworksDecodeOptions) -> Result<StormworksGeometry,StormworksParserError> {
+    let mut filetypemarker: [u8;4] = [0;4];
+    mesh_stream.read_exact(&mut filetypemarker)?;
+    if filetypemarker != *b"mesh" {
+        return Err(StormworksParserError::NotMesh);
+    }
+
+    let header0 = read_u16_from(&mut mesh_stream)?;
+    let header1 = read_u16_from(&mut mesh_stream)?;
+    let vertex_count = read_u16_from(&mut mesh_stream)? as u32;
+    let header3 = read_u16_from(&mut mesh_stream)?;
+    let header4 = read_u16_from(&mut mesh_stream)?;
+
+    let version = detect_format_version([header0, header1, header3, header4], &options.parse)?;
+    let bytes_per_vertex = version.bytes_per_vertex();
+
+    let mut vertex_bytes = vec![0_u8; vertex_count as usize * bytes_per_vertex];
+    mesh_stream.read_exact(&mut vertex_bytes)?;
+
+    let index_count = read_u32_from(&mut mesh_stream)?;
+    let file_indices = build_indices(&mut mesh_stream, index_count, vertex_count)?;
+
+    let sub_mesh_count = read_u16_from(&mut mesh_stream)? as u32;
+    let file_sub_meshes = build_sub_meshes(&mut mesh_stream, sub_mesh_count, index_count, &options.parse)?;
+
+    // Which file vertices to decode, in output order
+    let (vertex_order, indices, sub_meshes) = if options.sub_meshes == SubMeshFilter::All {
+        ((0..vertex_count).collect::<Vec<u32>>(), file_indices, file_sub_meshes)
+    } else {
+        let mut remap = vec![u32::MAX; vertex_count as usize];
+        let mut vertex_order = Vec::new();
+        let mut indices = Vec::new();
+        let mut sub_meshes = Vec::new();
+
+        for mut sub_mesh in file_sub_meshes.into_iter().filter(|sub_mesh| options.sub_meshes.matches(sub_mesh)) {
+            let start = sub_mesh.index_buffer_start as usize;
+            let end = start + sub_mesh.index_buffer_length as usize;
+
+            sub_mesh.index_buffer_start = indices.len() as u32;
+            for &index in &file_indices[start..end] {
+                if remap[index as usize] == u32::MAX {
+                    remap[index as usize] = vertex_order.len() as u32;
+                    vertex_order.push(index);
+                }
+                indices.push(remap[index as usize]);
+            }
+            sub_meshes.push(sub_mesh);
+        }
+        (vertex_order, indices, sub_meshes)
+    };
+
+    let record = |vertex: u32| &vertex_bytes[vertex as usize * bytes_per_vertex..(vertex as usize + 1) * bytes_per_vertex];
+
+    let positions = vertex_order
+        .iter()
+        .map(|&vertex| decode_vec3(&record(vertex)[version.position_offset()..]))
+        .collect();
+    let normals = options.normals.then(|| {
+        vertex_order
+            .iter()
+            .map(|&vertex| decode_vec3(&record(vertex)[version.normal_offset()..]))
+            .collect()
+    });
+    let colors = options.colors.then(|| {
+        vertex_order
+            .iter()
+            .map(|&vertex| {
+                let color = &record(vertex)[version.color_offset()..];
+                Rgba::new(color[0], color[1], color[2], color[3])
+            })
+            .collect()
+    });
+
+    Ok(StormworksGeometry {
+        version,
+        positions,
+        normals,
+        colors,
+        indices,
+        sub_meshes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::mat::repr_c::mat4::Mat4;
+    use crate::{parse_stormworks_mesh, primitive_box, primitive_sphere, write_stormworks_mesh, PrimitiveStyle, StormworksMesh};
+
+    fn fixture() -> Vec<u8> {
+        let emissive = PrimitiveStyle { shader: StormworksShaderType::Emissive, ..Default::default() };
+        let inputs = [
+            (primitive_box(Vec3::new(2, 3, 4), PrimitiveStyle::default()).unwrap(), Mat4::identity()),
+            (primitive_sphere(0.5, 8, 4, emissive).unwrap(), Mat4::translation_3d(Vec3::new(2.0, 0.0, 0.0))),
+        ];
+        let mut bytes = Vec::new();
+        write_stormworks_mesh(&StormworksMesh::merge(&inputs).unwrap().remove(0), &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn everything_matches_a_full_parse() {
+        let bytes = fixture();
+        let mesh = parse_stormworks_mesh(&bytes).unwrap();
+        let geometry = build_stormworks_geometry(&bytes[..], &StormworksDecodeOptions::default()).unwrap();
+
+        assert_eq!(geometry.positions, mesh.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>());
+        assert_eq!(geometry.normals, Some(mesh.vertices.iter().map(|vertex| vertex.normal).collect()));
+        assert_eq!(geometry.colors, Some(mesh.vertices.iter().map(|vertex| vertex.color).collect()));
+        assert_eq!(geometry.indices, mesh.indices);
+        assert_eq!(geometry.sub_meshes, mesh.sub_meshes);
+
+        let options = StormworksDecodeOptions { normals: false, colors: false, ..Default::default() };
+        let skipped = build_stormworks_geometry(&bytes[..], &options).unwrap();
+        assert_eq!((skipped.normals, skipped.colors), (None, None));
+        assert_eq!(skipped.positions, geometry.positions);
+    }
+
+    #[test]
+    fn filtered_sub_meshes_match_their_slices() {
+        let bytes = fixture();
+        let mesh = parse_stormworks_mesh(&bytes).unwrap();
+        let sphere = mesh.sub_meshes.iter().find(|sub_mesh| sub_mesh.shader_id == StormworksShaderType::Emissive).unwrap();
+        let range = sphere.index_buffer_start as usize..(sphere.index_buffer_start + sphere.index_buffer_length) as usize;
+
+        for filter in [SubMeshFilter::Name(sphere.name.clone()), SubMeshFilter::Shader(StormworksShaderType::Emissive)] {
+            let options = StormworksDecodeOptions { sub_meshes: filter, ..Default::default() };
+            let geometry = build_stormworks_geometry(&bytes[..], &options).unwrap();
+
+            // Same triangles, now starting at 0 and only using the vertices they need
+            assert_eq!(geometry.sub_meshes.len(), 1);
+            assert_eq!(geometry.sub_meshes[0].index_buffer_start, 0);
+            assert_eq!(geometry.sub_meshes[0].index_buffer_length, sphere.index_buffer_length);
+            assert_eq!(geometry.sub_meshes[0].name, sphere.name);
+            let normals = geometry.normals.as_ref().unwrap();
+            for (&index, &file_index) in geometry.indices.iter().zip(&mesh.indices[range.clone()]) {
+                let vertex = &mesh.vertices[file_index as usize];
+                assert_eq!((geometry.positions[index as usize], normals[index as usize]), (vertex.position, vertex.normal));
+            }
+            let mut used = mesh.indices[range.clone()].to_vec();
+            used.sort_unstable();
+            used.dedup();
+            assert_eq!(geometry.positions.len(), used.len());
+        }
+
+        let options = StormworksDecodeOptions { sub_meshes: SubMeshFilter::Name("missing".into()), ..Default::default() };
+        let geometry = build_stormworks_geometry(&bytes[..], &options).unwrap();
+        assert!(geometry.positions.is_empty() && geometry.indices.is_empty() && geometry.sub_meshes.is_empty());
+    }
+}
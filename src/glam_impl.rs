@@ -0,0 +1,55 @@
+use alloc::vec::Vec;
+
+use crate::{StormworksMeshVertexRecord, VertexColors, VertexNormals, VertexPositions};
+
+impl StormworksMeshVertexRecord {
+    pub fn glam_position(&self) -> glam::Vec3 {
+        glam::Vec3::from_array(self.position.into_array())
+    }
+    pub fn glam_normal(&self) -> glam::Vec3 {
+        glam::Vec3::from_array(self.normal.into_array())
+    }
+    // 0 to 1 per channel, still sRGB
+    pub fn glam_color(&self) -> glam::Vec4 {
+        glam::Vec4::from_array(self.color_f32().into_array())
+    }
+}
+
+impl From<VertexPositions<'_>> for Vec<glam::Vec3> {
+    fn from(positions: VertexPositions<'_>) -> Self {
+        positions.0.iter().map(StormworksMeshVertexRecord::glam_position).collect()
+    }
+}
+
+impl From<VertexNormals<'_>> for Vec<glam::Vec3> {
+    fn from(normals: VertexNormals<'_>) -> Self {
+        normals.0.iter().map(StormworksMeshVertexRecord::glam_normal).collect()
+    }
+}
+
+impl From<VertexColors<'_>> for Vec<glam::Vec4> {
+    fn from(colors: VertexColors<'_>) -> Self {
+        colors.0.iter().map(StormworksMeshVertexRecord::glam_color).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::{vec::repr_c::vec3::Vec3, Rgba};
+    use crate::{primitive_sphere, PrimitiveStyle};
+
+    #[test]
+    fn round_trip() {
+        let mesh = primitive_sphere(2.0, 8, 4, PrimitiveStyle { color: Rgba::new(10, 128, 200, 77), ..Default::default() }).unwrap();
+        let positions: Vec<glam::Vec3> = mesh.positions().into();
+        let normals: Vec<glam::Vec3> = mesh.normals().into();
+        let colors: Vec<glam::Vec4> = mesh.colors().into();
+
+        assert_eq!(positions.iter().map(|p| Vec3::from(p.to_array())).collect::<Vec<_>>(), mesh.positions().iter().collect::<Vec<_>>());
+        assert_eq!(normals.iter().map(|n| Vec3::from(n.to_array())).collect::<Vec<_>>(), mesh.normals().iter().collect::<Vec<_>>());
+        for (color, vertex) in colors.iter().zip(&mesh.vertices) {
+            assert_eq!(Rgba::<f32>::from(color.to_array()).map(|channel| (channel * 255.0).round() as u8), vertex.color);
+        }
+    }
+}
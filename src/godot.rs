@@ -0,0 +1,208 @@
+use std::fmt::Write;
+
+use vek::{vec::repr_c::vec3::Vec3, Rgba};
+
+use crate::{clamped_index_range, compact::octahedral_encode, StormworksMesh, StormworksShaderType};
+
+// Godot's Mesh::ArrayFormat bits, see RenderingServer::ArrayFormat
+const ARRAY_FORMAT_VERTEX: u64 = 1 << 0;
+const ARRAY_FORMAT_NORMAL: u64 = 1 << 1;
+const ARRAY_FORMAT_COLOR: u64 = 1 << 3;
+const ARRAY_FORMAT_INDEX: u64 = 1 << 12;
+const ARRAY_FLAG_FORMAT_VERSION_2: u64 = 1 << 35;
+const PRIMITIVE_TRIANGLES: u32 = 3;
+
+struct GodotSurface<'a> {
+    name: &'a str,
+    shader: &'a StormworksShaderType,
+    positions: Vec<Vec3<f32>>,
+    normals: Vec<Vec3<f32>>,
+    colors: Vec<Rgba<u8>>,
+    indices: Vec<u32>,
+}
+
+fn escape_godot_string(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn write_packed_byte_array(out: &mut String, bytes: &[u8]) {
+    out.push_str("PackedByteArray(");
+    for (i, byte) in bytes.iter().enumerate() {
+        if i != 0 {
+            out.push_str(", ");
+        }
+        let _ = write!(out, "{byte}");
+    }
+    out.push(')');
+}
+
+fn write_material(out: &mut String, id: usize, surface: &GodotSurface) {
+    let _ = writeln!(out, "[sub_resource type=\"StandardMaterial3D\" id=\"StandardMaterial3D_{id}\"]");
+    let _ = writeln!(out, "resource_name = \"{}\"", escape_godot_string(surface.name));
+    match surface.shader {
+        // Nothing is known about unknown shaders, opaque is the safest guess
+        StormworksShaderType::Opaque | StormworksShaderType::Unknown(_) => {}
+        StormworksShaderType::Transparent => {
+            let _ = writeln!(out, "transparency = 1");
+        }
+        // Godot can't take emission from vertex colors, so these glow white (or lava orange) and can be tinted after import
+        StormworksShaderType::Emissive => {
+            let _ = writeln!(out, "emission_enabled = true\nemission = Color(1, 1, 1, 1)");
+        }
+        StormworksShaderType::Lava => {
+            let _ = writeln!(out, "emission_enabled = true\nemission = Color(1, 0.3, 0, 1)");
+        }
+    }
+    let _ = writeln!(out, "vertex_color_use_as_albedo = true\nvertex_color_is_srgb = true\n");
+}
+
+fn write_surface(out: &mut String, material_id: usize, surface: &GodotSurface) {
+    let vertex_count = surface.positions.len();
+
+    // Version 2 vertex_data is all positions followed by all normals
+    let mut vertex_data = Vec::with_capacity(vertex_count * 16);
+    for position in &surface.positions {
+        for component in [position.x, position.y, position.z] {
+            vertex_data.extend_from_slice(&component.to_le_bytes());
+        }
+    }
+    for normal in &surface.normals {
+        // Godot's version 2 surfaces store normals octahedral encoded the same way CompactMesh does
+        for component in octahedral_encode(*normal) {
+            vertex_data.extend_from_slice(&component.to_le_bytes());
+        }
+    }
+
+    let attribute_data: Vec<u8> = surface.colors.iter().flat_map(|c| [c.r, c.g, c.b, c.a]).collect();
+
+    // Godot picks the index width from the vertex count of the surface
+    let mut index_data = Vec::with_capacity(surface.indices.len() * 4);
+    for index in &surface.indices {
+        if vertex_count <= 1 << 16 {
+            index_data.extend_from_slice(&(*index as u16).to_le_bytes());
+        } else {
+            index_data.extend_from_slice(&index.to_le_bytes());
+        }
+    }
+
+    let (mut min, mut max) = (surface.positions[0], surface.positions[0]);
+    for position in &surface.positions {
+        min = min.map2(*position, f32::min);
+        max = max.map2(*position, f32::max);
+    }
+    let size = max - min;
+
+    let format = ARRAY_FORMAT_VERTEX | ARRAY_FORMAT_NORMAL | ARRAY_FORMAT_COLOR | ARRAY_FORMAT_INDEX | ARRAY_FLAG_FORMAT_VERSION_2;
+
+    out.push_str("{\n");
+    let _ = writeln!(out, "\"aabb\": AABB({:?}, {:?}, {:?}, {:?}, {:?}, {:?}),", min.x, min.y, min.z, size.x, size.y, size.z);
+    out.push_str("\"attribute_data\": ");
+    write_packed_byte_array(out, &attribute_data);
+    out.push_str(",\n");
+    let _ = writeln!(out, "\"format\": {format},");
+    let _ = writeln!(out, "\"index_count\": {},", surface.indices.len());
+    out.push_str("\"index_data\": ");
+    write_packed_byte_array(out, &index_data);
+    out.push_str(",\n");
+    let _ = writeln!(out, "\"material\": SubResource(\"StandardMaterial3D_{material_id}\"),");
+    let _ = writeln!(out, "\"name\": \"{}\",", escape_godot_string(surface.name));
+    let _ = writeln!(out, "\"primitive\": {PRIMITIVE_TRIANGLES},");
+    let _ = writeln!(out, "\"vertex_count\": {vertex_count},");
+    out.push_str("\"vertex_data\": ");
+    write_packed_byte_array(out, &vertex_data);
+    out.push_str("\n}");
+}
+
+impl StormworksMesh {
+    // Godot surfaces each own their vertices, so every sub mesh gets just the vertices its index range uses.
+    // Axes are converted like the bevy conversion (X mirrored), and winding is flipped since Godot treats clockwise as front facing.
+    fn godot_surface<'a>(&self, name: &'a str, shader: &'a StormworksShaderType, index_range: std::ops::Range<usize>) -> Option<GodotSurface<'a>> {
+        let mut surface = GodotSurface {
+            name,
+            shader,
+            positions: Vec::new(),
+            normals: Vec::new(),
+            colors: Vec::new(),
+            indices: Vec::with_capacity(index_range.len()),
+        };
+        let mut remap = vec![u32::MAX; self.vertices.len()];
+
+        for triangle in self.indices[index_range].chunks_exact(3) {
+            // Hand built meshes haven't been through the parser's checks, triangles pointing past the vertices are left out
+            if triangle.iter().any(|&index| index as usize >= self.vertices.len()) {
+                continue;
+            }
+            for &index in [triangle[0], triangle[2], triangle[1]].iter() {
+                let index = index as usize;
+                if remap[index] == u32::MAX {
+                    let vertex = &self.vertices[index];
+                    remap[index] = surface.positions.len() as u32;
+                    surface.positions.push(Vec3::new(-vertex.position.x, vertex.position.y, vertex.position.z));
+                    surface.normals.push(Vec3::new(-vertex.normal.x, vertex.normal.y, vertex.normal.z));
+                    surface.colors.push(vertex.color);
+                }
+                surface.indices.push(remap[index]);
+            }
+        }
+
+        if surface.indices.is_empty() {
+            None
+        } else {
+            Some(surface)
+        }
+    }
+
+    // Exports as a text ArrayMesh resource with one surface and StandardMaterial3D per sub mesh
+    pub fn to_godot_tres(&self) -> String {
+        const WHOLE_MESH_SHADER: StormworksShaderType = StormworksShaderType::Opaque;
+
+        let mut surfaces = Vec::with_capacity(self.sub_meshes.len());
+        if self.sub_meshes.is_empty() {
+            // Nothing says which shader to use, but the geometry is still worth having
+            surfaces.extend(self.godot_surface("", &WHOLE_MESH_SHADER, 0..self.indices.len()));
+        }
+        for sub_mesh in &self.sub_meshes {
+            surfaces.extend(self.godot_surface(&sub_mesh.name, &sub_mesh.shader_id, clamped_index_range(sub_mesh, self.indices.len())));
+        }
+
+        let mut out = String::new();
+        let _ = writeln!(out, "[gd_resource type=\"ArrayMesh\" load_steps={} format=3]\n", surfaces.len() + 1);
+
+        for (id, surface) in surfaces.iter().enumerate() {
+            write_material(&mut out, id, surface);
+        }
+
+        out.push_str("[resource]\n_surfaces = [");
+        for (id, surface) in surfaces.iter().enumerate() {
+            if id != 0 {
+                out.push_str(", ");
+            }
+            write_surface(&mut out, id, surface);
+        }
+        out.push_str("]\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StormworksMeshVertexRecord;
+    use vek::Rgba;
+
+    #[test]
+    fn skips_triangles_with_out_of_range_indices() {
+        let vertex = |x: f32| StormworksMeshVertexRecord { position: Vec3::new(x, 0.0, 0.0), color: Rgba::white(), normal: Vec3::unit_y() };
+        let mesh = StormworksMesh {
+            vertex_count: 3,
+            vertices: vec![vertex(0.0), vertex(1.0), vertex(2.0)],
+            index_count: 6,
+            indices: vec![0, 1, 2, 0, 1, 7],
+            ..Default::default()
+        };
+        let surface = mesh.godot_surface("", &StormworksShaderType::Opaque, 0..6).unwrap();
+        assert_eq!(surface.indices, vec![0, 1, 2]);
+        assert_eq!(surface.positions.len(), 3);
+        assert!(mesh.to_godot_tres().contains("\"index_count\": 3,"));
+    }
+}
@@ -0,0 +1,182 @@
+use alloc::vec::Vec;
+
+use crate::{StormworksMesh, StormworksShaderType};
+
+// Tightly packed vertex ready for upload, 28 bytes like the file's own record:
+//
+// offset  size  attribute
+//      0    12  position, 3 x f32
+//     12    12  normal, 3 x f32
+//     24     4  color, 4 x unorm8 rgba (sRGB, as stored in the file)
+//
+// In wgpu terms: Float32x3 at 0, Float32x3 at 12, Unorm8x4 at 24, array stride 28.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "bytemuck", derive(bytemuck::Pod, bytemuck::Zeroable))]
+pub struct GpuVertex {
+    pub position: [f32;3],
+    pub normal: [f32;3],
+    pub color: [u8;4],
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum GpuIndices {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+impl GpuIndices {
+    pub fn len(&self) -> usize {
+        match self {
+            GpuIndices::U16(indices) => indices.len(),
+            GpuIndices::U32(indices) => indices.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // For APIs that only take one index type
+    pub fn into_u32(self) -> Vec<u32> {
+        match self {
+            GpuIndices::U16(indices) => indices.into_iter().map(u32::from).collect(),
+            GpuIndices::U32(indices) => indices,
+        }
+    }
+
+    #[cfg(feature = "bytemuck")]
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            GpuIndices::U16(indices) => bytemuck::cast_slice(indices),
+            GpuIndices::U32(indices) => bytemuck::cast_slice(indices),
+        }
+    }
+}
+
+// One draw call's worth of indices, matching a StormworksSubMesh
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GpuDrawRange {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub shader: StormworksShaderType,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GpuMeshBuffers {
+    pub vertices: Vec<GpuVertex>,
+    pub indices: GpuIndices,
+    pub draw_ranges: Vec<GpuDrawRange>,
+}
+impl GpuMeshBuffers {
+    #[cfg(feature = "bytemuck")]
+    pub fn vertex_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.vertices)
+    }
+}
+
+// The same vertex data split into one array per attribute
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StormworksMeshSoa {
+    pub positions: Vec<[f32;3]>,
+    pub normals: Vec<[f32;3]>,
+    pub colors: Vec<[u8;4]>,
+}
+#[cfg(feature = "bytemuck")]
+impl StormworksMeshSoa {
+    pub fn position_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.positions)
+    }
+    pub fn normal_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.normals)
+    }
+    pub fn color_bytes(&self) -> &[u8] {
+        bytemuck::cast_slice(&self.colors)
+    }
+}
+
+impl StormworksMesh {
+    // Indices are u16 whenever every index fits, which is always the case for meshes parsed from a file.
+    // Going by the indices rather than the vertex count means an out of range index is never truncated into a valid one.
+    pub fn to_gpu_buffers(&self) -> GpuMeshBuffers {
+        let vertices = self
+            .vertices
+            .iter()
+            .map(|vertex| GpuVertex {
+                position: vertex.position.into_array(),
+                normal: vertex.normal.into_array(),
+                color: vertex.color.into_array(),
+            })
+            .collect();
+
+        let indices = if self.indices.iter().all(|&index| index <= u16::MAX as u32) {
+            GpuIndices::U16(self.indices.iter().map(|&index| index as u16).collect())
+        } else {
+            GpuIndices::U32(self.indices.clone())
+        };
+
+        let draw_ranges = self
+            .sub_meshes
+            .iter()
+            .map(|sub_mesh| GpuDrawRange {
+                first_index: sub_mesh.index_buffer_start,
+                index_count: sub_mesh.index_buffer_length,
+                shader: sub_mesh.shader_id,
+            })
+            .collect();
+
+        GpuMeshBuffers { vertices, indices, draw_ranges }
+    }
+
+    pub fn to_soa(&self) -> StormworksMeshSoa {
+        StormworksMeshSoa {
+            positions: self.vertices.iter().map(|vertex| vertex.position.into_array()).collect(),
+            normals: self.vertices.iter().map(|vertex| vertex.normal.into_array()).collect(),
+            colors: self.vertices.iter().map(|vertex| vertex.color.into_array()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, PrimitiveStyle};
+    use vek::vec::repr_c::vec3::Vec3;
+
+    #[test]
+    fn buffers_match_the_mesh() {
+        let style = PrimitiveStyle { shader: StormworksShaderType::Transparent, ..Default::default() };
+        let mesh = primitive_box(Vec3::new(1, 2, 3), style).unwrap();
+        let buffers = mesh.to_gpu_buffers();
+
+        assert_eq!(buffers.vertices.len(), mesh.vertices.len());
+        for (gpu, vertex) in buffers.vertices.iter().zip(&mesh.vertices) {
+            assert_eq!(gpu.position, vertex.position.into_array());
+            assert_eq!(gpu.normal, vertex.normal.into_array());
+            assert_eq!(gpu.color, vertex.color.into_array());
+        }
+        assert!(matches!(buffers.indices, GpuIndices::U16(_)));
+        assert_eq!(buffers.indices.clone().into_u32(), mesh.indices);
+        assert_eq!(buffers.draw_ranges, [GpuDrawRange { first_index: 0, index_count: mesh.index_count, shader: StormworksShaderType::Transparent }]);
+
+        let soa = mesh.to_soa();
+        assert_eq!(soa.positions, buffers.vertices.iter().map(|vertex| vertex.position).collect::<Vec<_>>());
+        assert_eq!(soa.normals, buffers.vertices.iter().map(|vertex| vertex.normal).collect::<Vec<_>>());
+        assert_eq!(soa.colors, buffers.vertices.iter().map(|vertex| vertex.color).collect::<Vec<_>>());
+
+        #[cfg(feature = "bytemuck")]
+        {
+            assert_eq!(buffers.vertex_bytes().len(), mesh.vertices.len() * 28);
+            assert_eq!(buffers.indices.as_bytes().len(), mesh.indices.len() * 2);
+            assert_eq!(soa.color_bytes().len(), mesh.vertices.len() * 4);
+        }
+    }
+
+    #[test]
+    fn indices_too_big_for_u16_stay_u32() {
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        mesh.indices[0] = u16::MAX as u32 + 1;
+        let indices = mesh.to_gpu_buffers().indices;
+        assert_eq!(indices, GpuIndices::U32(mesh.indices.clone()));
+        assert_eq!(indices.len(), mesh.indices.len());
+    }
+}
@@ -1,10 +1,82 @@
 #![allow(private_interfaces)]
+#![cfg_attr(not(feature = "std"), no_std)]
 
-use std::{fs::File, io::{self, BufReader, Read, Seek, SeekFrom}};
+extern crate alloc;
+
+// Shared imports for the geometry modules. Float math is inherent on f32 with std, without it the same methods
+// come from libm through num_traits' Float, which only gets imported when it's needed.
+mod prelude {
+    pub(crate) use vek::vec::repr_c::vec3::Vec3;
+    #[cfg(not(any(feature = "std", test)))]
+    pub(crate) use vek::num_traits::Float;
+}
+
+use alloc::{boxed::Box, string::String, vec::Vec};
+#[cfg(feature = "std")]
+use std::{fs::File, io::{self, BufReader, Read}};
 use vek::{vec::repr_c::vec3::Vec3, Rgba};
 
 mod errors;
 pub use errors::*;
+mod format_version;
+pub use format_version::*;
+mod shader_registry;
+pub use shader_registry::*;
+mod decoder;
+pub use decoder::*;
+mod pool;
+pub use pool::*;
+mod text;
+mod gpu;
+pub use gpu::*;
+mod compact;
+pub use compact::*;
+mod views;
+pub use views::*;
+mod builder;
+pub use builder::*;
+mod primitives;
+pub use primitives::*;
+mod normals;
+pub use normals::*;
+mod weld;
+mod merge;
+mod transform;
+pub use transform::*;
+mod bounds;
+pub use bounds::*;
+mod bvh;
+pub use bvh::*;
+mod queries;
+pub use queries::*;
+mod collision;
+pub use collision::*;
+mod mass;
+pub use mass::*;
+#[cfg(feature = "glam")]
+mod glam_impl;
+#[cfg(feature = "nalgebra")]
+mod nalgebra_impl;
+#[cfg(feature = "mint")]
+mod mint_impl;
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+pub use writer::*;
+#[cfg(feature = "std")]
+mod scan;
+#[cfg(feature = "std")]
+pub use scan::*;
+#[cfg(feature = "std")]
+mod geometry;
+#[cfg(feature = "std")]
+pub use geometry::*;
+#[cfg(feature = "std")]
+mod usda;
+#[cfg(feature = "std")]
+mod godot;
+#[cfg(feature = "serde")]
+mod serde_impl;
 
 #[cfg(feature = "bevy-integration")]
 use bevy::{
@@ -12,9 +84,9 @@ use bevy::{
     prelude::Mesh,
     reflect::TypePath,
     render::mesh::{Indices, PrimitiveTopology},
-    asset::io::{Reader, AsyncSeekForwardExt},
-    asset::AsyncReadExt,
 };
+#[cfg(feature = "async")]
+use bevy::asset::{io::Reader, AsyncReadExt};
 
 
 
@@ -22,39 +94,90 @@ use bevy::{
 const BYTES_PER_COMPONENT: usize  = 4;
 const ENTRIES_PER_VERTEX: usize = 7;
 const BYTES_PER_VERTEX: usize = BYTES_PER_COMPONENT*ENTRIES_PER_VERTEX;
+// Biggest vertex record of any MeshFormatVersion, for stack buffers
+#[cfg(feature = "std")]
+const MAX_BYTES_PER_VERTEX: usize = BYTES_PER_VERTEX;
+
+
+// By default files whose version headers don't match any MeshFormatVersion fail with UnsupportedVersion,
+// set fallback_version to read them anyway.
+#[derive(Clone, Debug, Default)]
+pub struct StormworksParseOptions {
+    // Fail on shader ids that aren't one of the known StormworksShaderType variants, instead of keeping them as Unknown
+    pub reject_unknown_shaders: bool,
+    // Layout to read files with unrecognised version headers as, instead of failing with UnsupportedVersion.
+    // The headers themselves are kept on the mesh either way.
+    pub fallback_version: Option<MeshFormatVersion>,
+}
 
-
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StormworksMeshVertexRecord {
     pub position: Vec3<f32>,
     pub color: Rgba<u8>,
     pub normal: Vec3<f32>
 }
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StormworksShaderType {
-    Opaque = 0,
-    Transparent = 1,
-    Emissive = 2,
-    Lava = 3
+    Opaque,
+    Transparent,
+    Emissive,
+    Lava,
+    // A shader id this library doesn't know about yet, kept as is so the mesh can still be loaded and written back out
+    Unknown(u16)
 }
 impl StormworksShaderType {
-    fn from_u16(i: u16) -> Result<Self,InvalidStormworksShaderType> {
+    pub fn from_u16(i: u16) -> Self {
         match i {
-            0 => Ok(StormworksShaderType::Opaque),
-            1 => Ok(StormworksShaderType::Transparent),
-            2 => Ok(StormworksShaderType::Emissive),
-            3 => Ok(StormworksShaderType::Lava),
-            _ => Err(InvalidStormworksShaderType(i)),
+            0 => StormworksShaderType::Opaque,
+            1 => StormworksShaderType::Transparent,
+            2 => StormworksShaderType::Emissive,
+            3 => StormworksShaderType::Lava,
+            _ => StormworksShaderType::Unknown(i),
+        }
+    }
+    // from_u16, but only for the shader ids we know
+    pub fn from_u16_strict(i: u16) -> Result<Self,InvalidStormworksShaderType> {
+        match Self::from_u16(i) {
+            StormworksShaderType::Unknown(i) => Err(InvalidStormworksShaderType(i)),
+            shader => Ok(shader),
+        }
+    }
+    pub fn to_u16(&self) -> u16 {
+        match self {
+            StormworksShaderType::Opaque => 0,
+            StormworksShaderType::Transparent => 1,
+            StormworksShaderType::Emissive => 2,
+            StormworksShaderType::Lava => 3,
+            StormworksShaderType::Unknown(i) => *i,
         }
     }
 }
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StormworksSubMesh {
     pub index_buffer_start: u32,
     pub index_buffer_length: u32,
+    pub header2: u16,
     pub shader_id: StormworksShaderType,
+    pub bounds_min: Vec3<f32>,
+    pub bounds_max: Vec3<f32>,
+    pub header6: u16,
     pub name_length_bytes: u16,
     pub name: String,
+    pub header8: Vec3<f32>,
 }
+#[derive(Clone, Debug, PartialEq)]
 #[cfg_attr(feature = "bevy-integration", derive(Asset,TypePath))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "serde_impl::UncheckedStormworksMesh"))]
 pub struct StormworksMesh {
+    // Raw header values, kept so meshes can be written back out unchanged. See format_version() for what they mean.
+    pub header0: u16,
+    pub header1: u16,
+    pub header3: u16,
+    pub header4: u16,
     pub vertex_count: u32,
     pub vertices: Vec<StormworksMeshVertexRecord>,
     pub index_count: u32,
@@ -62,6 +185,93 @@ pub struct StormworksMesh {
     pub sub_mesh_count: u32,
     pub sub_meshes: Vec<StormworksSubMesh>,
 }
+// An empty mesh with V7 headers, so it can be written out as is
+impl Default for StormworksMesh {
+    fn default() -> Self {
+        let [header0, header1, header3, header4] = MeshFormatVersion::V7.headers();
+        StormworksMesh {
+            header0,
+            header1,
+            header3,
+            header4,
+            vertex_count: 0,
+            vertices: Vec::new(),
+            index_count: 0,
+            indices: Vec::new(),
+            sub_mesh_count: 0,
+            sub_meshes: Vec::new(),
+        }
+    }
+}
+impl StormworksMesh {
+    // None if the headers don't match any version this library can read
+    pub fn format_version(&self) -> Option<MeshFormatVersion> {
+        MeshFormatVersion::from_headers([self.header0, self.header1, self.header3, self.header4])
+    }
+
+    // Checks everything build_stormworks_mesh would reject, for meshes that didn't come from a .mesh file
+    pub fn validate(&self) -> Result<(),StormworksParserError> {
+        check_count("vertex_count", self.vertex_count, self.vertices.len())?;
+        check_count("index_count", self.index_count, self.indices.len())?;
+        check_count("sub_mesh_count", self.sub_mesh_count, self.sub_meshes.len())?;
+
+        // Both counts are stored as u16 in the file
+        check_format_limit("vertex_count", self.vertex_count, u16::MAX as u32)?;
+        check_format_limit("sub_mesh_count", self.sub_mesh_count, u16::MAX as u32)?;
+
+        self.check_indices()?;
+
+        for (i, sub_mesh) in self.sub_meshes.iter().enumerate() {
+            if sub_mesh.name_length_bytes > 1_000 {
+                return Err(TooBigNameLength.into());
+            }
+            check_count("name_length_bytes", sub_mesh.name_length_bytes as u32, sub_mesh.name.len())?;
+
+            check_sub_mesh_range(i as u32, sub_mesh, self.index_count)?;
+        }
+        Ok(())
+    }
+
+    // Only that every index points at a vertex, for the edits that look vertices up by index.
+    // Fields are public, so hand built meshes can get this wrong.
+    pub(crate) fn check_indices(&self) -> Result<(),IndexIndexOutOfBounds> {
+        match self.indices.iter().position(|&index| index as usize >= self.vertices.len()) {
+            Some(i) => Err(IndexIndexOutOfBounds { index: i as u32, vertex_count: self.vertices.len() as u32 }),
+            None => Ok(()),
+        }
+    }
+}
+// Start and length are both u32 from the file, so the end is summed in u64 where it can't overflow
+fn check_sub_mesh_range(submesh_id: u32, sub_mesh: &StormworksSubMesh, index_count: u32) -> Result<(),SubMeshIndexOutOfBounds> {
+    if sub_mesh.index_buffer_start > index_count {
+        return Err(SubMeshIndexOutOfBounds { submesh_id, index: sub_mesh.index_buffer_start, relevant_bound: index_count });
+    }
+    let end = sub_mesh.index_buffer_start as u64 + sub_mesh.index_buffer_length as u64;
+    if end > index_count as u64 {
+        return Err(SubMeshIndexOutOfBounds { submesh_id, index: end.min(u32::MAX as u64) as u32, relevant_bound: index_count });
+    }
+    Ok(())
+}
+// The sub mesh's indices clamped to 0..index_count, for hand built meshes that were never checked.
+// Summed in u64 like check_sub_mesh_range, so the end can't wrap on 32 bit targets either.
+pub(crate) fn clamped_index_range(sub_mesh: &StormworksSubMesh, index_count: usize) -> core::ops::Range<usize> {
+    let end = (sub_mesh.index_buffer_start as u64 + sub_mesh.index_buffer_length as u64).min(index_count as u64) as usize;
+    (sub_mesh.index_buffer_start as usize).min(end)..end
+}
+fn check_format_limit(field: &'static str, count: u32, limit: u32) -> Result<(),ExceedsFormatLimit> {
+    if count > limit {
+        return Err(ExceedsFormatLimit { field, count, limit });
+    }
+    Ok(())
+}
+fn check_count(field: &'static str, count: u32, actual: usize) -> Result<(),MismatchedCount> {
+    if count as usize != actual {
+        return Err(MismatchedCount { field, count, actual });
+    }
+    Ok(())
+}
+
+
 #[cfg(feature = "bevy-integration")]
 impl From<StormworksMesh> for Mesh {
     fn from(stormworks_mesh: StormworksMesh) -> Self {
@@ -91,7 +301,8 @@ impl From<StormworksMesh> for Mesh {
 }
 
 
-fn read_u16_from(reader: &mut BufReader<File>) -> Result<u16,io::Error> {
+#[cfg(feature = "std")]
+fn read_u16_from(reader: &mut impl Read) -> Result<u16,io::Error> {
     let mut byte_buffer: [u8;2] = [0;2];
     reader.read_exact(&mut byte_buffer)?;
     Ok(u16::from_le_bytes(byte_buffer))
@@ -105,7 +316,8 @@ async fn async_read_u16_from(reader: &mut dyn Reader) -> Result<u16,io::Error> {
 }
 
 
-fn read_u32_from(reader: &mut BufReader<File>) -> Result<u32,io::Error> {
+#[cfg(feature = "std")]
+fn read_u32_from(reader: &mut impl Read) -> Result<u32,io::Error> {
     let mut byte_buffer: [u8;4] = [0;4];
     reader.read_exact(&mut byte_buffer)?;
     Ok(u32::from_le_bytes(byte_buffer))
@@ -118,11 +330,30 @@ async fn async_read_u32_from(reader: &mut dyn Reader) -> Result<u32,io::Error> {
 }
 
 
-// Our version of `public VertexRecord(byte[] bytes)`
-fn build_vertex_record(mesh_stream: &mut BufReader<File>) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
+#[cfg(feature = "std")]
+fn read_vec3_from(reader: &mut impl Read) -> Result<Vec3<f32>,io::Error> {
+    let mut byte_buffer: [u8;12] = [0;12];
+    reader.read_exact(&mut byte_buffer)?;
+    Ok(Vec3::new(
+        f32::from_le_bytes([byte_buffer[0], byte_buffer[1], byte_buffer[2], byte_buffer[3]]),
+        f32::from_le_bytes([byte_buffer[4], byte_buffer[5], byte_buffer[6], byte_buffer[7]]),
+        f32::from_le_bytes([byte_buffer[8], byte_buffer[9], byte_buffer[10], byte_buffer[11]])
+    ))
+}
+#[cfg(feature = "async")]
+async fn async_read_vec3_from(reader: &mut dyn Reader) -> Result<Vec3<f32>,io::Error> {
+    let mut byte_buffer: [u8;12] = [0;12];
+    reader.read_exact(&mut byte_buffer).await?;
+    Ok(Vec3::new(
+        f32::from_le_bytes([byte_buffer[0], byte_buffer[1], byte_buffer[2], byte_buffer[3]]),
+        f32::from_le_bytes([byte_buffer[4], byte_buffer[5], byte_buffer[6], byte_buffer[7]]),
+        f32::from_le_bytes([byte_buffer[8], byte_buffer[9], byte_buffer[10], byte_buffer[11]])
+    ))
+}
 
-    let mut vertex_record_bytes = [0_u8;BYTES_PER_VERTEX];
-    mesh_stream.read_exact(&mut vertex_record_bytes)?;
+
+// Our version of `public VertexRecord(byte[] bytes)`, for the record layout of MeshFormatVersion::V7
+fn decode_v7_vertex_record(vertex_record_bytes: &[u8]) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
 
     let position_x = f32::from_le_bytes(vertex_record_bytes[0..=3].try_into()?);
     let position_y = f32::from_le_bytes(vertex_record_bytes[4..=7].try_into()?);
@@ -146,91 +377,122 @@ fn build_vertex_record(mesh_stream: &mut BufReader<File>) -> Result<StormworksMe
         normal
     })
 }
-#[cfg(feature = "async")]
-// Our async version of `public VertexRecord(byte[] bytes)`
-async fn async_build_vertex_record(mesh_stream: &mut dyn Reader) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
-
-    let mut vertex_record_bytes = [0_u8;BYTES_PER_VERTEX];
-    mesh_stream.read_exact(&mut vertex_record_bytes).await?;
-
-    let position_x = f32::from_le_bytes(vertex_record_bytes[0..=3].try_into()?);
-    let position_y = f32::from_le_bytes(vertex_record_bytes[4..=7].try_into()?);
-    let position_z = f32::from_le_bytes(vertex_record_bytes[8..=11].try_into()?);
-    let position = Vec3::new(position_x, position_y, position_z);
-
-    let red = vertex_record_bytes[12];
-    let green = vertex_record_bytes[13];
-    let blue = vertex_record_bytes[14];
-    let alpha = vertex_record_bytes[15];
-    let color = Rgba::new(red, green, blue, alpha);
+// Every vertex layout gets its own decode function, this picks the right one
+fn decode_vertex_record(vertex_record_bytes: &[u8], version: MeshFormatVersion) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
+    match version {
+        MeshFormatVersion::V7 => decode_v7_vertex_record(vertex_record_bytes),
+    }
+}
 
-    let normal_x = f32::from_le_bytes(vertex_record_bytes[16..=19].try_into()?);
-    let normal_y = f32::from_le_bytes(vertex_record_bytes[20..=23].try_into()?);
-    let normal_z = f32::from_le_bytes(vertex_record_bytes[24..=27].try_into()?);
-    let normal = Vec3::new(normal_x,normal_y,normal_z);
 
-    Ok(StormworksMeshVertexRecord {
-        position,
-        color,
-        normal
-    })
+#[cfg(feature = "std")]
+fn build_vertex_record(mesh_stream: &mut impl Read, version: MeshFormatVersion) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
+    let mut vertex_record_bytes = [0_u8;MAX_BYTES_PER_VERTEX];
+    let vertex_record_bytes = &mut vertex_record_bytes[..version.bytes_per_vertex()];
+    mesh_stream.read_exact(vertex_record_bytes)?;
+    decode_vertex_record(vertex_record_bytes, version)
+}
+#[cfg(feature = "async")]
+async fn async_build_vertex_record(mesh_stream: &mut dyn Reader, version: MeshFormatVersion) -> Result<StormworksMeshVertexRecord,Box<dyn SpecificError>> {
+    let mut vertex_record_bytes = [0_u8;MAX_BYTES_PER_VERTEX];
+    let vertex_record_bytes = &mut vertex_record_bytes[..version.bytes_per_vertex()];
+    mesh_stream.read_exact(vertex_record_bytes).await?;
+    decode_vertex_record(vertex_record_bytes, version)
 }
 
 
-fn build_vertices(mesh_stream: &mut BufReader<File>, vertex_count: u32) -> Result<Vec<StormworksMeshVertexRecord>,Box<dyn SpecificError>> {
-    let mut vertices = Vec::new();
+// The fill_ functions clear `into` and read into it, so the allocation from a previous mesh gets reused
+#[cfg(feature = "std")]
+fn fill_vertices(mesh_stream: &mut impl Read, vertex_count: u32, version: MeshFormatVersion, into: &mut Vec<StormworksMeshVertexRecord>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(vertex_count as usize);
     for _ in 0..vertex_count {
-        vertices.push(build_vertex_record(mesh_stream)?);
+        into.push(build_vertex_record(mesh_stream, version)?);
     }
-    return Ok(vertices)
+    Ok(())
 }
 #[cfg(feature = "async")]
-async fn async_build_vertices(mesh_stream: &mut dyn Reader, vertex_count: u32) -> Result<Vec<StormworksMeshVertexRecord>,Box<dyn SpecificError>> {
-    let mut vertices = Vec::new();
+async fn async_fill_vertices(mesh_stream: &mut dyn Reader, vertex_count: u32, version: MeshFormatVersion, into: &mut Vec<StormworksMeshVertexRecord>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(vertex_count as usize);
     for _ in 0..vertex_count {
-        vertices.push(async_build_vertex_record(mesh_stream).await?);
+        into.push(async_build_vertex_record(mesh_stream, version).await?);
     }
-    return Ok(vertices)
+    Ok(())
 }
 
 
-fn build_indices(mesh_stream: &mut BufReader<File>, index_count: u32, vertex_count: u32) -> Result<Vec<u32>,Box<dyn SpecificError>> {
-    let mut indices = Vec::new();
+// index_count is a u32 straight from the file, so a corrupt one shouldn't get to decide how much is reserved up front
+const MAX_INDEX_RESERVATION: u32 = 1 << 20;
+
+#[cfg(feature = "std")]
+fn fill_indices(mesh_stream: &mut impl Read, index_count: u32, vertex_count: u32, into: &mut Vec<u32>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(index_count.min(MAX_INDEX_RESERVATION) as usize);
     for i in 0..index_count {
         let index = read_u16_from(mesh_stream)? as u32;
         if index >= vertex_count {
             return Err(IndexIndexOutOfBounds { index: i, vertex_count }.into());
         }
-        indices.push(index);
+        into.push(index);
     }
-    return Ok(indices)
+    Ok(())
 }
 #[cfg(feature = "async")]
-async fn async_build_indices(mesh_stream: &mut dyn Reader, index_count: u32, vertex_count: u32) -> Result<Vec<u32>,Box<dyn SpecificError>> {
-    let mut indices = Vec::new();
+async fn async_fill_indices(mesh_stream: &mut dyn Reader, index_count: u32, vertex_count: u32, into: &mut Vec<u32>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(index_count.min(MAX_INDEX_RESERVATION) as usize);
     for i in 0..index_count {
         let index = async_read_u16_from(mesh_stream).await? as u32;
         if index >= vertex_count {
             return Err(IndexIndexOutOfBounds { index: i, vertex_count }.into());
         }
-        indices.push(index);
+        into.push(index);
     }
-    return Ok(indices)
+    Ok(())
 }
+#[cfg(feature = "std")]
+fn build_indices(mesh_stream: &mut impl Read, index_count: u32, vertex_count: u32) -> Result<Vec<u32>,Box<dyn SpecificError>> {
+    let mut indices = Vec::new();
+    fill_indices(mesh_stream, index_count, vertex_count, &mut indices)?;
+    Ok(indices)
+}
+
 
+fn detect_format_version(headers: [u16;4], options: &StormworksParseOptions) -> Result<MeshFormatVersion,StormworksParserError> {
+    match (MeshFormatVersion::from_headers(headers), options.fallback_version) {
+        (Some(version), _) | (None, Some(version)) => Ok(version),
+        (None, None) => Err(StormworksParserError::UnsupportedVersion { headers }),
+    }
+}
 
-fn build_sub_mesh(mesh_stream: &mut BufReader<File>) -> Result<StormworksSubMesh,Box<dyn SpecificError>> {
+
+fn shader_from_u16(i: u16, options: &StormworksParseOptions) -> Result<StormworksShaderType,InvalidStormworksShaderType> {
+    if options.reject_unknown_shaders {
+        StormworksShaderType::from_u16_strict(i)
+    } else {
+        Ok(StormworksShaderType::from_u16(i))
+    }
+}
+
+
+#[cfg(feature = "std")]
+fn build_sub_mesh(mesh_stream: &mut impl Read, options: &StormworksParseOptions) -> Result<StormworksSubMesh,Box<dyn SpecificError>> {
     let index_buffer_start = read_u32_from(mesh_stream)?;
 
     let index_buffer_length = read_u32_from(mesh_stream)?;
 
-    mesh_stream.seek(SeekFrom::Current(2))?; // Header 2
+    let header2 = read_u16_from(mesh_stream)?;
 
-    let shader_id = StormworksShaderType::from_u16(
-        read_u16_from(mesh_stream)?
+    let shader_id = shader_from_u16(
+        read_u16_from(mesh_stream)?,
+        options
     )?;
 
-    mesh_stream.seek(SeekFrom::Current(4*3+4*3+2))?;
+    let bounds_min = read_vec3_from(mesh_stream)?;
+    let bounds_max = read_vec3_from(mesh_stream)?;
+
+    let header6 = read_u16_from(mesh_stream)?;
 
     let name_length_bytes = read_u16_from(mesh_stream)?;
     
@@ -238,38 +500,44 @@ fn build_sub_mesh(mesh_stream: &mut BufReader<File>) -> Result<StormworksSubMesh
         return Err(TooBigNameLength.into());
     }
 
-    let mut name_buf = Vec::with_capacity(name_length_bytes as usize);
-    for _ in 0..name_length_bytes {
-        name_buf.push(0);
-    }
+    let mut name_buf = vec![0; name_length_bytes as usize];
     mesh_stream.read_exact(&mut name_buf)?;
     
     let name = String::from_utf8(name_buf)?;
 
-    mesh_stream.seek(SeekFrom::Current(4*3))?; // Header 8
+    let header8 = read_vec3_from(mesh_stream)?;
     
     Ok(StormworksSubMesh {
         index_buffer_start,
         index_buffer_length,
+        header2,
         shader_id,
+        bounds_min,
+        bounds_max,
+        header6,
         name_length_bytes,
-        name
+        name,
+        header8
     })
 }
 #[cfg(feature = "async")]
-async fn async_build_sub_mesh(mesh_stream: &mut dyn Reader) -> Result<StormworksSubMesh,Box<dyn SpecificError>> {
+async fn async_build_sub_mesh(mesh_stream: &mut dyn Reader, options: &StormworksParseOptions) -> Result<StormworksSubMesh,Box<dyn SpecificError>> {
 
     let index_buffer_start = async_read_u32_from(mesh_stream).await?;
 
     let index_buffer_length = async_read_u32_from(mesh_stream).await?;
 
-    mesh_stream.seek_forward(2).await?; // Header 2
+    let header2 = async_read_u16_from(mesh_stream).await?;
 
-    let shader_id = StormworksShaderType::from_u16(
-        async_read_u16_from(mesh_stream).await?
+    let shader_id = shader_from_u16(
+        async_read_u16_from(mesh_stream).await?,
+        options
     )?;
 
-    mesh_stream.seek_forward(4*3+4*3+2).await?;
+    let bounds_min = async_read_vec3_from(mesh_stream).await?;
+    let bounds_max = async_read_vec3_from(mesh_stream).await?;
+
+    let header6 = async_read_u16_from(mesh_stream).await?;
 
     let name_length_bytes = async_read_u16_from(mesh_stream).await?;
     
@@ -277,144 +545,247 @@ async fn async_build_sub_mesh(mesh_stream: &mut dyn Reader) -> Result<Stormworks
         return Err(TooBigNameLength.into());
     }
 
-    let mut name_buf = Vec::with_capacity(name_length_bytes as usize);
-    for _ in 0..name_length_bytes {
-        name_buf.push(0);
-    }
+    let mut name_buf = vec![0; name_length_bytes as usize];
     mesh_stream.read_exact(&mut name_buf).await?;
     
     let name = String::from_utf8(name_buf)?;
 
-    mesh_stream.seek_forward(4*3).await?; // Header 8
+    let header8 = async_read_vec3_from(mesh_stream).await?;
     
     Ok(StormworksSubMesh {
         index_buffer_start,
         index_buffer_length,
+        header2,
         shader_id,
+        bounds_min,
+        bounds_max,
+        header6,
         name_length_bytes,
-        name
+        name,
+        header8
     })
 }
 
 
-fn build_sub_meshes(mesh_stream: &mut BufReader<File>, sub_mesh_count: u32, index_count: u32) -> Result<Vec<StormworksSubMesh>,Box<dyn SpecificError>> {
-    let mut sub_meshes = Vec::with_capacity(sub_mesh_count as usize);
+#[cfg(feature = "std")]
+fn fill_sub_meshes(mesh_stream: &mut impl Read, sub_mesh_count: u32, index_count: u32, options: &StormworksParseOptions, into: &mut Vec<StormworksSubMesh>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(sub_mesh_count as usize);
     for i in 0..sub_mesh_count {
-        let sub_mesh = build_sub_mesh(mesh_stream)?;
-        
-        if sub_mesh.index_buffer_start > index_count {
-            return Err(SubMeshIndexOutOfBounds { submesh_id: i, index: sub_mesh.index_buffer_start, relevant_bound: index_count }.into())
-        }
+        let sub_mesh = build_sub_mesh(mesh_stream, options)?;
 
-        if sub_mesh.index_buffer_start + sub_mesh.index_buffer_length > index_count {
-            return Err(SubMeshIndexOutOfBounds { submesh_id: i, index: sub_mesh.index_buffer_start + sub_mesh.index_buffer_length, relevant_bound: index_count }.into())
-        }
-        
-        sub_meshes.push(sub_mesh);
+        check_sub_mesh_range(i, &sub_mesh, index_count)?;
+
+        into.push(sub_mesh);
     }
-    return Ok(sub_meshes)
+    Ok(())
 }
 #[cfg(feature = "async")]
-async fn async_build_sub_meshes(mesh_stream: &mut dyn Reader, sub_mesh_count: u32, index_count: u32) -> Result<Vec<StormworksSubMesh>,Box<dyn SpecificError>> {
-    let mut sub_meshes = Vec::with_capacity(sub_mesh_count as usize);
+async fn async_fill_sub_meshes(mesh_stream: &mut dyn Reader, sub_mesh_count: u32, index_count: u32, options: &StormworksParseOptions, into: &mut Vec<StormworksSubMesh>) -> Result<(),Box<dyn SpecificError>> {
+    into.clear();
+    into.reserve(sub_mesh_count as usize);
     for i in 0..sub_mesh_count {
-        let sub_mesh = async_build_sub_mesh(mesh_stream).await?;
-        
-        if sub_mesh.index_buffer_start > index_count {
-            return Err(SubMeshIndexOutOfBounds { submesh_id: i, index: sub_mesh.index_buffer_start, relevant_bound: index_count }.into())
-        }
+        let sub_mesh = async_build_sub_mesh(mesh_stream, options).await?;
 
-        if sub_mesh.index_buffer_start + sub_mesh.index_buffer_length > index_count {
-            return Err(SubMeshIndexOutOfBounds { submesh_id: i, index: sub_mesh.index_buffer_start + sub_mesh.index_buffer_length, relevant_bound: index_count }.into())
-        }
-        
-        sub_meshes.push(sub_mesh);
+        check_sub_mesh_range(i, &sub_mesh, index_count)?;
+
+        into.push(sub_mesh);
     }
-    return Ok(sub_meshes)
+    Ok(())
+}
+#[cfg(feature = "std")]
+fn build_sub_meshes(mesh_stream: &mut impl Read, sub_mesh_count: u32, index_count: u32, options: &StormworksParseOptions) -> Result<Vec<StormworksSubMesh>,Box<dyn SpecificError>> {
+    let mut sub_meshes = Vec::new();
+    fill_sub_meshes(mesh_stream, sub_mesh_count, index_count, options, &mut sub_meshes)?;
+    Ok(sub_meshes)
 }
 
 
 // our version of `public static Mesh LoadMesh(Stream stream, MeshDiagCallback diag = null)`
-pub fn build_stormworks_mesh(mut mesh_stream: BufReader<File>) -> Result<StormworksMesh,StormworksParserError> {
-    // first 4 bytes are 4 chars, the file type header 'mesh'
-    let mut filetypemarker: [u8;4] = [0;4];
-    mesh_stream.read_exact(&mut filetypemarker)?;
-    if filetypemarker != *b"mesh" {
-        return Err(StormworksParserError::NotMesh);
-        
+#[cfg(feature = "std")]
+pub fn build_stormworks_mesh(mesh_stream: BufReader<File>) -> Result<StormworksMesh,StormworksParserError> {
+    build_stormworks_mesh_with_options(mesh_stream, &StormworksParseOptions::default())
+}
+#[cfg(feature = "std")]
+pub fn build_stormworks_mesh_with_options(mesh_stream: BufReader<File>, options: &StormworksParseOptions) -> Result<StormworksMesh,StormworksParserError> {
+    let mut mesh = StormworksMesh::default();
+    mesh.read_into_with_options(mesh_stream, options)?;
+    Ok(mesh)
+}
+#[cfg(feature = "async")]
+// our async version of `public static Mesh LoadMesh(Stream stream, MeshDiagCallback diag = null)`
+pub async fn async_build_stormworks_mesh(mesh_stream: &mut dyn Reader) -> Result<StormworksMesh,StormworksParserError> {
+    async_build_stormworks_mesh_with_options(mesh_stream, &StormworksParseOptions::default()).await
+}
+#[cfg(feature = "async")]
+pub async fn async_build_stormworks_mesh_with_options(mesh_stream: &mut dyn Reader, options: &StormworksParseOptions) -> Result<StormworksMesh,StormworksParserError> {
+    let mut mesh = StormworksMesh::default();
+    mesh.async_read_into_with_options(mesh_stream, options).await?;
+    Ok(mesh)
+}
+
+
+// Parsing into an existing mesh, replacing its contents while keeping its allocations.
+// If parsing fails the mesh is left with whatever was read so far, it's only good for reading into again.
+impl StormworksMesh {
+    #[cfg(feature = "std")]
+    pub fn read_into(&mut self, mesh_stream: impl Read) -> Result<(),StormworksParserError> {
+        self.read_into_with_options(mesh_stream, &StormworksParseOptions::default())
     }
+    #[cfg(feature = "std")]
+    pub fn read_into_with_options(&mut self, mut mesh_stream: impl Read, options: &StormworksParseOptions) -> Result<(),StormworksParserError> {
+        // first 4 bytes are 4 chars, the file type header 'mesh'
+        let mut filetypemarker: [u8;4] = [0;4];
+        mesh_stream.read_exact(&mut filetypemarker)?;
+        if filetypemarker != *b"mesh" {
+            return Err(StormworksParserError::NotMesh);
+        }
 
-    // the following 4 bytes are header0 and header1
-    mesh_stream.seek(SeekFrom::Current(4))?;
+        // the following 4 bytes are header0 and header1
+        self.header0 = read_u16_from(&mut mesh_stream)?;
+        self.header1 = read_u16_from(&mut mesh_stream)?;
 
-    let vertex_count = read_u16_from(&mut mesh_stream)? as u32;
+        // the following 2 bytes are vertex_count
+        self.vertex_count = read_u16_from(&mut mesh_stream)? as u32;
 
-    // the following 4 bytes are header3 and header4
-    mesh_stream.seek(SeekFrom::Current(4))?;
+        // the following 4 bytes are header3 and header4
+        self.header3 = read_u16_from(&mut mesh_stream)?;
+        self.header4 = read_u16_from(&mut mesh_stream)?;
 
-    let vertices = build_vertices(&mut mesh_stream, vertex_count)?;
+        let version = detect_format_version([self.header0, self.header1, self.header3, self.header4], options)?;
 
-    // on to indices
-    let index_count = read_u32_from(&mut mesh_stream)?;
+        fill_vertices(&mut mesh_stream, self.vertex_count, version, &mut self.vertices)?;
 
-    let indices = build_indices(&mut mesh_stream, index_count, vertex_count)?;
+        // on to indices
+        self.index_count = read_u32_from(&mut mesh_stream)?;
 
-    // on to submeshes
-    let sub_mesh_count = read_u16_from(&mut mesh_stream)? as u32;
-    
-    let sub_meshes = build_sub_meshes(&mut mesh_stream, sub_mesh_count, index_count)?;
+        fill_indices(&mut mesh_stream, self.index_count, self.vertex_count, &mut self.indices)?;
 
-    // end of data
+        // on to submeshes
+        self.sub_mesh_count = read_u16_from(&mut mesh_stream)? as u32;
 
-    Result::Ok(StormworksMesh {
-        vertex_count, // the following 2 bytes are vertex_count
-        vertices, // the following 
-        index_count,
-        indices,
-        sub_mesh_count,
-        sub_meshes
-    })
-}
-#[cfg(feature = "async")]
-// our async version of `public static Mesh LoadMesh(Stream stream, MeshDiagCallback diag = null)`
-pub async fn async_build_stormworks_mesh(mesh_stream: &mut dyn Reader) -> Result<StormworksMesh,StormworksParserError> {
+        fill_sub_meshes(&mut mesh_stream, self.sub_mesh_count, self.index_count, options, &mut self.sub_meshes)?;
 
-    // first 4 bytes are 4 chars, the file type header 'mesh'
-    let mut filetypemarker: [u8;4] = [0;4];
-    mesh_stream.read_exact(&mut filetypemarker).await?;
-    if filetypemarker != *b"mesh" {
-        return Err(StormworksParserError::NotMesh);
-        
+        // end of data
+        Ok(())
     }
 
-    // the following 4 bytes are header0 and header1
-    mesh_stream.seek_forward(4).await?;
+    #[cfg(feature = "async")]
+    pub async fn async_read_into(&mut self, mesh_stream: &mut dyn Reader) -> Result<(),StormworksParserError> {
+        self.async_read_into_with_options(mesh_stream, &StormworksParseOptions::default()).await
+    }
+    #[cfg(feature = "async")]
+    pub async fn async_read_into_with_options(&mut self, mesh_stream: &mut dyn Reader, options: &StormworksParseOptions) -> Result<(),StormworksParserError> {
+        // first 4 bytes are 4 chars, the file type header 'mesh'
+        let mut filetypemarker: [u8;4] = [0;4];
+        mesh_stream.read_exact(&mut filetypemarker).await?;
+        if filetypemarker != *b"mesh" {
+            return Err(StormworksParserError::NotMesh);
+        }
 
-    let vertex_count = async_read_u16_from(mesh_stream).await? as u32;
+        // the following 4 bytes are header0 and header1
+        self.header0 = async_read_u16_from(mesh_stream).await?;
+        self.header1 = async_read_u16_from(mesh_stream).await?;
 
-    // the following 4 bytes are header3 and header4
-    mesh_stream.seek_forward(4).await?;
+        // the following 2 bytes are vertex_count
+        self.vertex_count = async_read_u16_from(mesh_stream).await? as u32;
 
-    let vertices = async_build_vertices(mesh_stream, vertex_count).await?;
+        // the following 4 bytes are header3 and header4
+        self.header3 = async_read_u16_from(mesh_stream).await?;
+        self.header4 = async_read_u16_from(mesh_stream).await?;
 
-    // on to indices
-    let index_count = async_read_u32_from(mesh_stream).await?;
+        let version = detect_format_version([self.header0, self.header1, self.header3, self.header4], options)?;
 
-    let indices = async_build_indices(mesh_stream, index_count, vertex_count).await?;
+        async_fill_vertices(mesh_stream, self.vertex_count, version, &mut self.vertices).await?;
 
-    // on to submeshes
-    let sub_mesh_count = async_read_u16_from(mesh_stream).await? as u32;
-    
-    let sub_meshes = async_build_sub_meshes(mesh_stream, sub_mesh_count, index_count).await?;
+        // on to indices
+        self.index_count = async_read_u32_from(mesh_stream).await?;
 
-    // end of data
+        async_fill_indices(mesh_stream, self.index_count, self.vertex_count, &mut self.indices).await?;
 
-    Result::Ok(StormworksMesh {
-        vertex_count, // the following 2 bytes are vertex_count
-        vertices, // the following 
-        index_count,
-        indices,
-        sub_mesh_count,
-        sub_meshes
-    })
-}
\ No newline at end of file
+        // on to submeshes
+        self.sub_mesh_count = async_read_u16_from(mesh_stream).await? as u32;
+
+        async_fill_sub_meshes(mesh_stream, self.sub_mesh_count, self.index_count, options, &mut self.sub_meshes).await?;
+
+        // end of data
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A mesh with one vertex, one index and one sub mesh covering start..start + length
+    fn mesh_bytes(headers: [u16;4], start: u32, length: u32) -> Vec<u8> {
+        let mut bytes = b"mesh".to_vec();
+        for value in [headers[0], headers[1], 1, headers[2], headers[3]] {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0; BYTES_PER_VERTEX]);
+        bytes.extend_from_slice(&1_u32.to_le_bytes());
+        bytes.extend_from_slice(&0_u16.to_le_bytes());
+        bytes.extend_from_slice(&1_u16.to_le_bytes());
+        bytes.extend_from_slice(&start.to_le_bytes());
+        bytes.extend_from_slice(&length.to_le_bytes());
+        bytes.extend_from_slice(&[0; 2 + 2 + 24 + 2 + 2 + 12]);
+        bytes
+    }
+
+    #[test]
+    fn overflowing_sub_mesh_range_is_rejected() {
+        let bytes = mesh_bytes([7, 1, 0x13, 0], 1, u32::MAX);
+        assert!(matches!(parse_stormworks_mesh(&bytes), Err(StormworksParserError::CorruptFile(_))));
+        #[cfg(feature = "std")]
+        assert!(matches!(StormworksMesh::default().read_into(&bytes[..]), Err(StormworksParserError::CorruptFile(_))));
+
+        assert!(parse_stormworks_mesh(&mesh_bytes([7, 1, 0x13, 0], 1, 0)).is_ok());
+    }
+
+    #[test]
+    fn clamped_index_range_never_wraps() {
+        let mut sub_mesh = parse_stormworks_mesh(&mesh_bytes([7, 1, 0x13, 0], 0, 1)).unwrap().sub_meshes.remove(0);
+        assert_eq!(clamped_index_range(&sub_mesh, 6), 0..1);
+        (sub_mesh.index_buffer_start, sub_mesh.index_buffer_length) = (3, u32::MAX);
+        assert_eq!(clamped_index_range(&sub_mesh, 6), 3..6);
+        (sub_mesh.index_buffer_start, sub_mesh.index_buffer_length) = (u32::MAX, u32::MAX);
+        assert_eq!(clamped_index_range(&sub_mesh, 6), 6..6);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn async_parsing_matches_blocking() {
+        let bytes = mesh_bytes([7, 1, 0x13, 0], 1, 0);
+        let mut reader = bevy::asset::io::VecReader::new(bytes.clone());
+        let mesh = futures::executor::block_on(async_build_stormworks_mesh(&mut reader)).unwrap();
+        assert_eq!(mesh, parse_stormworks_mesh(&bytes).unwrap());
+
+        let mut reader = bevy::asset::io::VecReader::new(mesh_bytes([7, 1, 0x13, 0], 1, u32::MAX));
+        assert!(futures::executor::block_on(async_build_stormworks_mesh(&mut reader)).is_err());
+    }
+
+    #[test]
+    fn unknown_headers_are_rejected_unless_a_fallback_is_set() {
+        let lenient = StormworksParseOptions { fallback_version: Some(MeshFormatVersion::V7), ..Default::default() };
+        for headers in [[7, 1, 0x13, 0], [8, 1, 0x13, 0], [7, 2, 0x17, 5], [0, 0, 0, 0]] {
+            let bytes = mesh_bytes(headers, 1, 0);
+            let known = headers == [7, 1, 0x13, 0];
+            match parse_stormworks_mesh(&bytes) {
+                Ok(mesh) => assert!(known && mesh.format_version() == Some(MeshFormatVersion::V7)),
+                Err(StormworksParserError::UnsupportedVersion { headers: rejected }) => assert!(!known && rejected == headers),
+                Err(err) => panic!("{err:?}"),
+            }
+
+            let mesh = parse_stormworks_mesh_with_options(&bytes, &lenient).unwrap();
+            assert_eq!([mesh.header0, mesh.header1, mesh.header3, mesh.header4], headers);
+            assert_eq!(mesh.vertices.len(), 1);
+            assert_eq!(mesh.format_version().is_some(), known);
+            #[cfg(feature = "std")]
+            {
+                assert_eq!(StormworksMesh::default().read_into(&bytes[..]).is_ok(), known);
+                assert!(StormworksMesh::default().read_into_with_options(&bytes[..], &lenient).is_ok());
+            }
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use vek::{mat::repr_c::mat3::Mat3, vec::repr_c::vec3::Vec3};
+
+use crate::{clamped_index_range, bounds::symmetric_eigenvectors, normals::triangle_normal, StormworksMesh};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MassProperties {
+    // Negative when the mesh is inside out, mass and inertia then come out negative too
+    pub volume: f32,
+    pub surface_area: f32,
+    pub mass: f32,
+    pub centroid: Vec3<f32>,
+    // About the centroid, in the mesh's axes
+    pub inertia: Mat3<f32>,
+    // Smallest moment first, the axes are orthonormal and right handed
+    pub principal_moments: Vec3<f32>,
+    pub principal_axes: [Vec3<f32>;3],
+    // Edges without a matching edge running the other way. Anything but 0 means the mesh isn't closed and everything except surface_area is unreliable.
+    pub open_edges: usize,
+}
+
+impl MassProperties {
+    pub fn is_closed(&self) -> bool {
+        self.open_edges == 0
+    }
+
+    // Triangles as they're wound in Stormworks, clockwise when seen from outside
+    fn from_triangles(triangles: impl Iterator<Item = [Vec3<f32>;3]>, density: f32) -> Option<MassProperties> {
+        // Edges are matched by exact position, since vertices are duplicated wherever normals or colors change
+        let key = |p: Vec3<f32>| [p.x, p.y, p.z].map(|c| (c + 0.0).to_bits());
+        let mut edges: BTreeMap<([u32;3], [u32;3]), i32> = BTreeMap::new();
+
+        let (mut volume, mut surface_area) = (0.0f32, 0.0f32);
+        let mut first_moment = Vec3::<f32>::zero();
+        // Second moment about the reference point, integral of p p^T over the volume
+        let mut covariance = Mat3::<f32>::zero();
+        // Everything is summed relative to a point on the mesh. Fanning from the world origin instead would lose
+        // most of f32's precision to cancellation for parts far away from it.
+        let mut reference = None;
+        for [a, b, c] in triangles {
+            surface_area += triangle_normal(a, b, c).magnitude() / 2.0;
+            for (p, q) in [(a, b), (b, c), (c, a)] {
+                let (p, q) = (key(p), key(q));
+                if p != q {
+                    *edges.entry(if p < q { (p, q) } else { (q, p) }).or_default() += if p < q { 1 } else { -1 };
+                }
+            }
+
+            // Divergence theorem as a sum of tetrahedra from the reference point to each triangle, a c b is the counter clockwise order
+            let origin = *reference.get_or_insert(a);
+            let (a, b, c) = (a - origin, b - origin, c - origin);
+            let determinant = a.dot(c.cross(b));
+            let sum = a + b + c;
+            volume += determinant / 6.0;
+            first_moment += sum * (determinant / 24.0);
+            covariance += (outer(sum, sum) + outer(a, a) + outer(b, b) + outer(c, c)) * (determinant / 120.0);
+        }
+        let reference = reference?;
+        if volume == 0.0 || !volume.is_finite() {
+            return None;
+        }
+
+        let offset = first_moment / volume;
+        let centroid = reference + offset;
+        let covariance = covariance - outer(offset, offset) * volume;
+        let trace = covariance.cols.x.x + covariance.cols.y.y + covariance.cols.z.z;
+        let inertia = (Mat3::<f32>::identity() * trace - covariance) * density;
+
+        let mut principal: [(f32, Vec3<f32>);3] = symmetric_eigenvectors(inertia).map(|axis| {
+            let axis = axis.normalized();
+            (axis.dot(inertia * axis), axis)
+        });
+        principal.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let [(x_moment, x), (y_moment, y), (z_moment, _)] = principal;
+        // Rebuilt from the cross product so the axes stay orthonormal and right handed despite rounding
+        let z = x.cross(y).normalized();
+        let y = z.cross(x);
+
+        Some(MassProperties {
+            volume,
+            surface_area,
+            mass: volume * density,
+            centroid,
+            inertia,
+            principal_moments: Vec3::new(x_moment, y_moment, z_moment),
+            principal_axes: [x, y, z],
+            open_edges: edges.values().filter(|&&balance| balance != 0).count(),
+        })
+    }
+}
+
+fn outer(a: Vec3<f32>, b: Vec3<f32>) -> Mat3<f32> {
+    Mat3::new(a.x * b.x, a.x * b.y, a.x * b.z, a.y * b.x, a.y * b.y, a.y * b.z, a.z * b.x, a.z * b.y, a.z * b.z)
+}
+
+impl StormworksMesh {
+    fn triangles_in(&self, start: usize, end: usize) -> impl Iterator<Item = [Vec3<f32>;3]> + '_ {
+        let start = start.min(self.indices.len());
+        let end = end.clamp(start, self.indices.len());
+        self.indices[start..end]
+            .chunks_exact(3)
+            .filter_map(|triangle| Some([self.vertices.get(triangle[0] as usize)?.position, self.vertices.get(triangle[1] as usize)?.position, self.vertices.get(triangle[2] as usize)?.position]))
+    }
+
+    // Volume, mass, centroid and inertia of the solid the mesh encloses, with density in mass per cubic meter.
+    // None when there's no volume to weigh. Check is_closed on the result, an open mesh still gives numbers but they don't mean much.
+    pub fn mass_properties(&self, density: f32) -> Option<MassProperties> {
+        MassProperties::from_triangles(self.triangles_in(0, self.indices.len()), density)
+    }
+
+    // Same as mass_properties but for one sub mesh on its own, which has to be closed by itself
+    pub fn sub_mesh_mass_properties(&self, sub_mesh: usize, density: f32) -> Option<MassProperties> {
+        let sub_mesh = self.sub_meshes.get(sub_mesh)?;
+        let range = clamped_index_range(sub_mesh, self.indices.len());
+        MassProperties::from_triangles(self.triangles_in(range.start, range.end), density)
+    }
+
+    // In sub mesh order
+    pub fn mass_properties_per_sub_mesh(&self, density: f32) -> Vec<Option<MassProperties>> {
+        (0..self.sub_meshes.len()).map(|sub_mesh| self.sub_mesh_mass_properties(sub_mesh, density)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, PrimitiveStyle};
+    use vek::{mat::repr_c::mat4::Mat4, quaternion::repr_c::Quaternion};
+
+    fn unit_cube() -> StormworksMesh {
+        let mut cube = primitive_box(Vec3::new(4, 4, 4), PrimitiveStyle::default()).unwrap();
+        cube.center_on(crate::MeshAnchor::BoundsCenter);
+        cube
+    }
+
+    fn assert_close(a: f32, b: f32, tolerance: f32) {
+        assert!((a - b).abs() <= tolerance, "{a} != {b}");
+    }
+
+    #[test]
+    fn unit_cube_properties() {
+        let properties = unit_cube().mass_properties(1.0).unwrap();
+        assert!(properties.is_closed());
+        assert_close(properties.volume, 1.0, 1e-6);
+        assert_close(properties.mass, 1.0, 1e-6);
+        assert_close(properties.surface_area, 6.0, 1e-5);
+        assert!(properties.centroid.magnitude() < 1e-6);
+        for (row, expected) in properties.inertia.into_row_arrays().iter().zip([[1.0 / 6.0, 0.0, 0.0], [0.0, 1.0 / 6.0, 0.0], [0.0, 0.0, 1.0 / 6.0]]) {
+            for (&value, expected) in row.iter().zip(expected) {
+                assert_close(value, expected, 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn density_scales_mass_and_inertia() {
+        let properties = unit_cube().mass_properties(2.5).unwrap();
+        assert_close(properties.volume, 1.0, 1e-6);
+        assert_close(properties.mass, 2.5, 1e-5);
+        assert_close(properties.principal_moments.x, 2.5 / 6.0, 1e-5);
+    }
+
+    #[test]
+    fn open_mesh_is_reported() {
+        let mut cube = unit_cube();
+        cube.indices.truncate(cube.indices.len() - 3);
+        cube.index_count -= 3;
+        let properties = cube.mass_properties(1.0).unwrap();
+        assert!(!properties.is_closed());
+        assert_eq!(properties.open_edges, 3);
+    }
+
+    #[test]
+    fn inside_out_mesh_has_negative_volume() {
+        let mut cube = unit_cube();
+        for triangle in cube.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        assert_close(cube.mass_properties(1.0).unwrap().volume, -1.0, 1e-6);
+    }
+
+    #[test]
+    fn far_from_the_origin() {
+        let mut cube = unit_cube();
+        cube.translate(Vec3::new(2000.0, -1000.0, 3000.0));
+        let properties = cube.mass_properties(1.0).unwrap();
+        assert_close(properties.volume, 1.0, 1e-3);
+        assert!(properties.centroid.distance(Vec3::new(2000.0, -1000.0, 3000.0)) < 1e-3);
+        for moment in properties.principal_moments {
+            assert_close(moment, 1.0 / 6.0, 1e-3);
+        }
+    }
+
+    #[test]
+    fn principal_axes_of_a_small_rotated_box() {
+        // 2 by 1 by 0.5 millimeters at a low density, inertia in the order of 1e-13
+        let mut part = primitive_box(Vec3::new(8, 4, 2), PrimitiveStyle::default()).unwrap();
+        part.center_on(crate::MeshAnchor::BoundsCenter);
+        let rotation = Quaternion::rotation_y(0.4) * Quaternion::rotation_x(0.7);
+        part.transform(Mat4::from(rotation) * Mat4::scaling_3d(Vec3::broadcast(1e-3)));
+        let properties = part.mass_properties(10.0).unwrap();
+
+        let mass = 10.0 * 1e-9;
+        let expected = [(1.0 + 0.25) / 12.0, (4.0 + 0.25) / 12.0, (4.0 + 1.0) / 12.0].map(|moment| moment * mass * 1e-6);
+        for (moment, expected) in properties.principal_moments.into_iter().zip(expected) {
+            assert_close(moment, expected, expected * 1e-3);
+        }
+        for (axis, expected) in properties.principal_axes.iter().zip([Vec3::unit_x(), Vec3::unit_y(), Vec3::unit_z()]) {
+            assert_close(axis.dot(rotation * expected).abs(), 1.0, 1e-4);
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use alloc::{string::String, vec::Vec};
+
+use vek::{mat::repr_c::mat4::Mat4, vec::repr_c::vec3::Vec3};
+
+use crate::{clamped_index_range, transform::{mirrors, transformed_vertices}, StormworksMesh, StormworksMeshVertexRecord, StormworksParserError, StormworksShaderType, StormworksSubMesh};
+
+// Largest vertex count a .mesh file can hold
+const MAX_VERTICES: usize = u16::MAX as usize;
+
+struct MergeGroup<'a> {
+    name: &'a str,
+    shader: StormworksShaderType,
+    template: Option<&'a StormworksSubMesh>,
+    // (input, first index of the triangle)
+    triangles: Vec<(usize, usize)>,
+}
+
+// Collects output meshes, starting a new one whenever the next triangle wouldn't fit
+struct MergeOutput {
+    headers: [u16;4],
+    meshes: Vec<StormworksMesh>,
+    current: StormworksMesh,
+    // Per input, where each of its vertices ended up in the current mesh
+    remap: Vec<Vec<u32>>,
+    touched: Vec<(usize, usize)>,
+}
+
+impl MergeOutput {
+    fn new_mesh(headers: [u16;4]) -> StormworksMesh {
+        let [header0, header1, header3, header4] = headers;
+        StormworksMesh { header0, header1, header3, header4, ..Default::default() }
+    }
+
+    fn flush(&mut self) {
+        let mut mesh = core::mem::replace(&mut self.current, Self::new_mesh(self.headers));
+        for (input, vertex) in self.touched.drain(..) {
+            self.remap[input][vertex] = u32::MAX;
+        }
+        // A sub mesh begun right before the split may have gotten nothing
+        mesh.sub_meshes.retain(|sub_mesh| sub_mesh.index_buffer_length > 0);
+        mesh.vertex_count = mesh.vertices.len() as u32;
+        mesh.index_count = mesh.indices.len() as u32;
+        mesh.sub_mesh_count = mesh.sub_meshes.len() as u32;
+        if !mesh.indices.is_empty() {
+            self.meshes.push(mesh);
+        }
+    }
+
+    fn begin_sub_mesh(&mut self, group: &MergeGroup) {
+        let template = group.template;
+        self.current.sub_meshes.push(StormworksSubMesh {
+            index_buffer_start: self.current.indices.len() as u32,
+            index_buffer_length: 0,
+            header2: template.map_or(0, |t| t.header2),
+            shader_id: group.shader,
+            bounds_min: Vec3::broadcast(f32::INFINITY),
+            bounds_max: Vec3::broadcast(f32::NEG_INFINITY),
+            header6: template.map_or(0, |t| t.header6),
+            name_length_bytes: group.name.len().min(u16::MAX as usize) as u16,
+            name: String::from(group.name),
+            header8: template.map_or(Vec3::zero(), |t| t.header8),
+        });
+    }
+
+    fn push_triangle(&mut self, group: &MergeGroup, input: usize, corners: [u32;3], vertices: &[StormworksMeshVertexRecord]) {
+        let new_vertices = corners.iter().filter(|&&corner| self.remap[input][corner as usize] == u32::MAX).count();
+        if self.current.vertices.len() + new_vertices > MAX_VERTICES {
+            self.flush();
+            if group.template.is_some() {
+                self.begin_sub_mesh(group);
+            }
+        }
+
+        for corner in corners {
+            let slot = &mut self.remap[input][corner as usize];
+            if *slot == u32::MAX {
+                *slot = self.current.vertices.len() as u32;
+                self.current.vertices.push(vertices[corner as usize].clone());
+                self.touched.push((input, corner as usize));
+            }
+            let index = *slot;
+            self.current.indices.push(index);
+
+            if group.template.is_some() {
+                let position = vertices[corner as usize].position;
+                if let Some(sub_mesh) = self.current.sub_meshes.last_mut() {
+                    sub_mesh.index_buffer_length += 1;
+                    sub_mesh.bounds_min = Vec3::partial_min(sub_mesh.bounds_min, position);
+                    sub_mesh.bounds_max = Vec3::partial_max(sub_mesh.bounds_max, position);
+                }
+            }
+        }
+    }
+}
+
+impl StormworksMesh {
+    // Bakes placed meshes into as few meshes as possible. Sub meshes with the same name and shader are combined into one contiguous range.
+    // A .mesh file holds at most 65535 vertices, past that the result is split into more meshes, each with its own share of the sub meshes.
+    // Triangles that weren't in any sub mesh stay outside of sub meshes, after all of them.
+    pub fn merge(inputs: &[(StormworksMesh, Mat4<f32>)]) -> Result<Vec<StormworksMesh>, StormworksParserError> {
+        for (mesh, _) in inputs {
+            mesh.validate()?;
+        }
+
+        let transformed: Vec<(Vec<StormworksMeshVertexRecord>, bool)> = inputs
+            .iter()
+            .map(|(mesh, transform)| (transformed_vertices(&mesh.vertices, *transform), mirrors(*transform)))
+            .collect();
+
+        let mut groups: Vec<MergeGroup> = Vec::new();
+        let mut loose = MergeGroup { name: "", shader: StormworksShaderType::Opaque, template: None, triangles: Vec::new() };
+        for (input, (mesh, _)) in inputs.iter().enumerate() {
+            for first in (0..mesh.indices.len() / 3 * 3).step_by(3) {
+                // Sub meshes could overlap, a triangle goes with the first one it's in so it's never baked twice
+                let owner = mesh.sub_meshes.iter().find(|sub_mesh| clamped_index_range(sub_mesh, mesh.indices.len()).contains(&first));
+                let group = match owner {
+                    None => &mut loose,
+                    Some(sub_mesh) => match groups.iter().position(|group| group.name == sub_mesh.name && group.shader == sub_mesh.shader_id) {
+                        Some(i) => &mut groups[i],
+                        None => {
+                            groups.push(MergeGroup { name: &sub_mesh.name, shader: sub_mesh.shader_id, template: Some(sub_mesh), triangles: Vec::new() });
+                            groups.last_mut().unwrap()
+                        }
+                    },
+                };
+                group.triangles.push((input, first));
+            }
+        }
+
+        let headers = inputs.first().map_or(crate::MeshFormatVersion::V7.headers(), |(mesh, _)| [mesh.header0, mesh.header1, mesh.header3, mesh.header4]);
+        let mut output = MergeOutput {
+            headers,
+            meshes: Vec::new(),
+            current: MergeOutput::new_mesh(headers),
+            remap: inputs.iter().map(|(mesh, _)| alloc::vec![u32::MAX; mesh.vertices.len()]).collect(),
+            touched: Vec::new(),
+        };
+
+        groups.push(loose);
+        for group in &groups {
+            if group.template.is_some() {
+                output.begin_sub_mesh(group);
+            }
+            for &(input, first) in &group.triangles {
+                let indices = &inputs[input].0.indices;
+                let (vertices, mirrored) = &transformed[input];
+                let corners = if *mirrored {
+                    [indices[first], indices[first + 2], indices[first + 1]]
+                } else {
+                    [indices[first], indices[first + 1], indices[first + 2]]
+                };
+                output.push_triangle(group, input, corners, vertices);
+            }
+        }
+        output.flush();
+        Ok(output.meshes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{normals::triangle_normal, primitive_box, primitive_sphere, PrimitiveStyle};
+
+    fn corners(mesh: &StormworksMesh) -> impl Iterator<Item = [Vec3<f32>;3]> + '_ {
+        mesh.indices.chunks_exact(3).map(|triangle| [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize].position))
+    }
+    fn volume(mesh: &StormworksMesh) -> f32 {
+        corners(mesh).map(|[a, b, c]| a.dot(c.cross(b)) / 6.0).sum()
+    }
+    fn surface_area(mesh: &StormworksMesh) -> f32 {
+        corners(mesh).map(|[a, b, c]| triangle_normal(a, b, c).magnitude() / 2.0).sum()
+    }
+
+    fn positions(mesh: &StormworksMesh, range: core::ops::Range<usize>) -> Vec<Vec3<f32>> {
+        mesh.indices[range].iter().map(|&index| mesh.vertices[index as usize].position).collect()
+    }
+
+    #[test]
+    fn remaps_indices_and_groups_sub_meshes() {
+        let style = PrimitiveStyle::default();
+        let a = primitive_box(Vec3::one(), style).unwrap();
+        let b = primitive_box(Vec3::one(), PrimitiveStyle { shader: StormworksShaderType::Emissive, ..style }).unwrap();
+        let offset = Vec3::new(5.0, 0.0, 0.0);
+        let inputs = [(a.clone(), Mat4::identity()), (b, Mat4::identity()), (a.clone(), Mat4::translation_3d(offset))];
+
+        let merged = StormworksMesh::merge(&inputs).unwrap();
+        assert_eq!(merged.len(), 1);
+        let merged = &merged[0];
+        merged.validate().unwrap();
+        assert_eq!(merged.vertices.len(), a.vertices.len() * 3);
+
+        // Same name and shader share a sub mesh, in the order they were first seen
+        assert_eq!(merged.sub_meshes.len(), 2);
+        let (opaque, emissive) = (&merged.sub_meshes[0], &merged.sub_meshes[1]);
+        assert_eq!(opaque.shader_id, StormworksShaderType::Opaque);
+        assert_eq!(emissive.shader_id, StormworksShaderType::Emissive);
+        assert_eq!(opaque.index_buffer_length as usize, a.indices.len() * 2);
+        assert_eq!(emissive.index_buffer_start, opaque.index_buffer_length);
+
+        // The third input's triangles come right after the first's, moved and pointing at their own vertices
+        let n = a.indices.len();
+        assert_eq!(positions(merged, 0..n), positions(&a, 0..n));
+        let moved: Vec<_> = positions(&a, 0..n).into_iter().map(|position| position + offset).collect();
+        assert_eq!(positions(merged, n..2 * n), moved);
+        assert_eq!(opaque.bounds_max, a.sub_meshes[0].bounds_max + offset);
+    }
+
+    #[test]
+    fn loose_triangles_go_last_and_mirrored_inputs_are_rewound() {
+        let mut loose = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        loose.sub_meshes.clear();
+        loose.sub_mesh_count = 0;
+        let boxed = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+
+        let mirror = Mat4::scaling_3d(Vec3::new(-1.0, 1.0, 1.0));
+        let merged = StormworksMesh::merge(&[(loose.clone(), mirror), (boxed.clone(), Mat4::identity())]).unwrap().remove(0);
+        assert_eq!(merged.sub_meshes.len(), 1);
+        assert_eq!(merged.sub_meshes[0].index_buffer_start, 0);
+        assert_eq!(merged.sub_meshes[0].index_buffer_length as usize, boxed.indices.len());
+
+        let n = boxed.indices.len();
+        let mut tail = merged.clone();
+        tail.indices.drain(..n);
+        tail.index_count = tail.indices.len() as u32;
+        tail.sub_meshes.clear();
+        assert!((volume(&tail) - volume(&loose)).abs() < 1e-6);
+        assert_eq!(tail.normal_deviation_report().flipped_triangles, 0);
+    }
+
+    #[test]
+    fn splits_at_the_vertex_limit() {
+        let sphere = primitive_sphere(1.0, 128, 64, PrimitiveStyle::default()).unwrap();
+        let copies = MAX_VERTICES / sphere.vertices.len() + 1;
+        let inputs: Vec<_> = (0..copies).map(|i| (sphere.clone(), Mat4::translation_3d(Vec3::new(i as f32 * 3.0, 0.0, 0.0)))).collect();
+
+        let merged = StormworksMesh::merge(&inputs).unwrap();
+        assert_eq!(merged.len(), 2);
+        let (mut triangles, mut area) = (0, 0.0);
+        for mesh in &merged {
+            mesh.validate().unwrap();
+            assert!(mesh.vertices.len() <= MAX_VERTICES);
+            // The split can fall in the middle of a sub mesh, both halves keep it
+            assert_eq!(mesh.sub_meshes.len(), 1);
+            triangles += mesh.indices.len() / 3;
+            area += surface_area(mesh);
+        }
+        assert_eq!(triangles, copies * sphere.indices.len() / 3);
+        let expected = copies as f32 * surface_area(&sphere);
+        // Loose, the moved copies round differently
+        assert!((area - expected).abs() < expected * 1e-3);
+    }
+}
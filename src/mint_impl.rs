@@ -0,0 +1,76 @@
+use alloc::vec::Vec;
+
+use crate::{StormworksMeshVertexRecord, VertexColors, VertexNormals, VertexPositions};
+
+impl StormworksMeshVertexRecord {
+    pub fn mint_position(&self) -> mint::Point3<f32> {
+        self.position.into_array().into()
+    }
+    pub fn mint_normal(&self) -> mint::Vector3<f32> {
+        self.normal.into_array().into()
+    }
+    // 0 to 1 per channel, still sRGB
+    pub fn mint_color(&self) -> mint::Vector4<f32> {
+        self.color_f32().into_array().into()
+    }
+}
+
+// mint is what math libraries agree on, so collect_as works for any of them that convert from mint (cgmath, ultraviolet, ...)
+impl VertexPositions<'_> {
+    pub fn collect_as<T: From<mint::Point3<f32>>>(&self) -> Vec<T> {
+        self.0.iter().map(|vertex| T::from(vertex.mint_position())).collect()
+    }
+}
+
+impl VertexNormals<'_> {
+    pub fn collect_as<T: From<mint::Vector3<f32>>>(&self) -> Vec<T> {
+        self.0.iter().map(|vertex| T::from(vertex.mint_normal())).collect()
+    }
+}
+
+impl VertexColors<'_> {
+    pub fn collect_as<T: From<mint::Vector4<f32>>>(&self) -> Vec<T> {
+        self.0.iter().map(|vertex| T::from(vertex.mint_color())).collect()
+    }
+}
+
+impl From<VertexPositions<'_>> for Vec<mint::Point3<f32>> {
+    fn from(positions: VertexPositions<'_>) -> Self {
+        positions.collect_as()
+    }
+}
+
+impl From<VertexNormals<'_>> for Vec<mint::Vector3<f32>> {
+    fn from(normals: VertexNormals<'_>) -> Self {
+        normals.collect_as()
+    }
+}
+
+impl From<VertexColors<'_>> for Vec<mint::Vector4<f32>> {
+    fn from(colors: VertexColors<'_>) -> Self {
+        colors.collect_as()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::{vec::repr_c::vec3::Vec3, Rgba};
+    use crate::{primitive_sphere, PrimitiveStyle};
+
+    #[test]
+    fn round_trip() {
+        let mesh = primitive_sphere(2.0, 8, 4, PrimitiveStyle { color: Rgba::new(10, 128, 200, 77), ..Default::default() }).unwrap();
+        let positions: Vec<mint::Point3<f32>> = mesh.positions().into();
+        let normals: Vec<mint::Vector3<f32>> = mesh.normals().into();
+        let colors: Vec<mint::Vector4<f32>> = mesh.colors().into();
+
+        assert_eq!(positions.iter().map(|&p| Vec3::from(<[f32;3]>::from(p))).collect::<Vec<_>>(), mesh.positions().iter().collect::<Vec<_>>());
+        // vek converts from mint itself, which is what collect_as is for
+        assert_eq!(mesh.normals().collect_as::<Vec3<f32>>(), mesh.normals().iter().collect::<Vec<_>>());
+        assert_eq!(normals.into_iter().map(Vec3::from).collect::<Vec<_>>(), mesh.normals().iter().collect::<Vec<_>>());
+        for (&color, vertex) in colors.iter().zip(&mesh.vertices) {
+            assert_eq!(Rgba::<f32>::from(<[f32;4]>::from(color)).map(|channel| (channel * 255.0).round() as u8), vertex.color);
+        }
+    }
+}
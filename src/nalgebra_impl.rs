@@ -0,0 +1,56 @@
+use alloc::vec::Vec;
+
+use crate::{StormworksMeshVertexRecord, VertexColors, VertexNormals, VertexPositions};
+
+impl StormworksMeshVertexRecord {
+    pub fn nalgebra_position(&self) -> nalgebra::Point3<f32> {
+        nalgebra::Point3::new(self.position.x, self.position.y, self.position.z)
+    }
+    pub fn nalgebra_normal(&self) -> nalgebra::Vector3<f32> {
+        nalgebra::Vector3::new(self.normal.x, self.normal.y, self.normal.z)
+    }
+    // 0 to 1 per channel, still sRGB
+    pub fn nalgebra_color(&self) -> nalgebra::Vector4<f32> {
+        let color = self.color_f32();
+        nalgebra::Vector4::new(color.r, color.g, color.b, color.a)
+    }
+}
+
+impl From<VertexPositions<'_>> for Vec<nalgebra::Point3<f32>> {
+    fn from(positions: VertexPositions<'_>) -> Self {
+        positions.0.iter().map(StormworksMeshVertexRecord::nalgebra_position).collect()
+    }
+}
+
+impl From<VertexNormals<'_>> for Vec<nalgebra::Vector3<f32>> {
+    fn from(normals: VertexNormals<'_>) -> Self {
+        normals.0.iter().map(StormworksMeshVertexRecord::nalgebra_normal).collect()
+    }
+}
+
+impl From<VertexColors<'_>> for Vec<nalgebra::Vector4<f32>> {
+    fn from(colors: VertexColors<'_>) -> Self {
+        colors.0.iter().map(StormworksMeshVertexRecord::nalgebra_color).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::{vec::repr_c::vec3::Vec3, Rgba};
+    use crate::{primitive_sphere, PrimitiveStyle};
+
+    #[test]
+    fn round_trip() {
+        let mesh = primitive_sphere(2.0, 8, 4, PrimitiveStyle { color: Rgba::new(10, 128, 200, 77), ..Default::default() }).unwrap();
+        let positions: Vec<nalgebra::Point3<f32>> = mesh.positions().into();
+        let normals: Vec<nalgebra::Vector3<f32>> = mesh.normals().into();
+        let colors: Vec<nalgebra::Vector4<f32>> = mesh.colors().into();
+
+        assert_eq!(positions.iter().map(|p| Vec3::new(p.x, p.y, p.z)).collect::<Vec<_>>(), mesh.positions().iter().collect::<Vec<_>>());
+        assert_eq!(normals.iter().map(|n| Vec3::new(n.x, n.y, n.z)).collect::<Vec<_>>(), mesh.normals().iter().collect::<Vec<_>>());
+        for (color, vertex) in colors.iter().zip(&mesh.vertices) {
+            assert_eq!(Rgba::new(color.x, color.y, color.z, color.w).map(|channel| (channel * 255.0).round() as u8), vertex.color);
+        }
+    }
+}
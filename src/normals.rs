@@ -0,0 +1,249 @@
+use alloc::vec::Vec;
+
+use crate::{prelude::*, StormworksMesh, StormworksParserError};
+
+// Angles are in radians. Corners sharing a position are smoothed together unless their faces meet at more than crease_angle,
+// in which case the vertex gets split so each side keeps its own normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalMode {
+    // Every triangle gets its own face normal
+    Flat,
+    // Bigger triangles pull harder on the shared normal
+    AreaWeighted { crease_angle: f32 },
+    // Each face counts by the angle of its corner at the vertex, which doesn't depend on how faces were tessellated
+    AngleWeighted { crease_angle: f32 },
+}
+
+// Outward facing, with length twice the triangle's area.
+// Front faces are clockwise in the game's left handed coordinates, so the usual (b - a) x (c - a) points inwards and gets negated.
+pub(crate) fn triangle_normal(a: Vec3<f32>, b: Vec3<f32>, c: Vec3<f32>) -> Vec3<f32> {
+    (c - a).cross(b - a)
+}
+
+// atan2 keeps precision for tiny angles, where acos of the dot product rounds to 0
+pub(crate) fn angle_between(a: Vec3<f32>, b: Vec3<f32>) -> f32 {
+    a.cross(b).magnitude().atan2(a.dot(b))
+}
+
+// -0.0 and 0.0 are the same position
+fn position_key(position: Vec3<f32>) -> [u32;3] {
+    (position + Vec3::zero()).map(f32::to_bits).into_array()
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NormalDeviationReport {
+    // Largest angle between a corner's stored normal and its triangle's geometric normal, None for degenerate triangles or zero length stored normals
+    pub per_triangle: Vec<Option<f32>>,
+    pub max_deviation: f32,
+    pub mean_deviation: f32,
+    // Triangles with no area or an out of range index, so no geometric normal to compare against
+    pub degenerate_triangles: usize,
+    // Triangles where some stored normal points more than 90 degrees away, usually a flipped normal or winding
+    pub flipped_triangles: usize,
+}
+
+impl StormworksMesh {
+    // Fails without changing anything if an index is out of range
+    pub fn recompute_normals(&mut self, mode: NormalMode) -> Result<(),StormworksParserError> {
+        self.check_indices()?;
+
+        let triangle_count = self.indices.len() / 3;
+        let face_normals: Vec<Vec3<f32>> = self
+            .indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|i| self.vertices[i as usize].position);
+                triangle_normal(a, b, c)
+            })
+            .collect();
+
+        // What each corner contributes to the normals of the corners it gets smoothed with
+        let corner_weights: Vec<Vec3<f32>> = (0..triangle_count * 3)
+            .map(|corner| {
+                let face_normal = face_normals[corner / 3];
+                match mode {
+                    NormalMode::Flat => face_normal,
+                    NormalMode::AreaWeighted { .. } => face_normal,
+                    NormalMode::AngleWeighted { .. } => {
+                        let triangle = corner / 3 * 3;
+                        let position = |k: usize| self.vertices[self.indices[triangle + k] as usize].position;
+                        let k = corner % 3;
+                        let (here, next, previous) = (position(k), position((k + 1) % 3), position((k + 2) % 3));
+                        face_normal.normalized() * angle_between(next - here, previous - here)
+                    }
+                }
+            })
+            .map(|weight| if weight.x.is_finite() && weight.y.is_finite() && weight.z.is_finite() { weight } else { Vec3::zero() })
+            .collect();
+
+        let mut corner_normals: Vec<Option<Vec3<f32>>> = Vec::with_capacity(triangle_count * 3);
+        match mode {
+            NormalMode::Flat => corner_normals.extend(corner_weights.iter().map(|&weight| Some(weight))),
+            NormalMode::AreaWeighted { crease_angle } | NormalMode::AngleWeighted { crease_angle } => {
+                let min_cos = crease_angle.cos();
+
+                let mut corners_by_position: Vec<([u32;3], usize)> = (0..triangle_count * 3)
+                    .map(|corner| (position_key(self.vertices[self.indices[corner] as usize].position), corner))
+                    .collect();
+                corners_by_position.sort_unstable();
+
+                corner_normals.resize(triangle_count * 3, None);
+                for group in corners_by_position.chunk_by(|a, b| a.0 == b.0) {
+                    for &(_, corner) in group {
+                        let own = face_normals[corner / 3].normalized();
+                        let mut sum = Vec3::zero();
+                        // Summed in the same order for every corner, so corners with the same neighbours end up bit for bit equal and can share a vertex
+                        for &(_, other) in group {
+                            if face_normals[other / 3].normalized().dot(own) >= min_cos {
+                                sum += corner_weights[other];
+                            }
+                        }
+                        corner_normals[corner] = Some(sum);
+                    }
+                }
+            }
+        }
+
+        // Corners of the same vertex that came out with different normals need their own copies of it
+        let original_vertex_count = self.vertices.len();
+        let mut assigned: Vec<Vec<(Vec3<f32>, u32)>> = alloc::vec![Vec::new(); original_vertex_count];
+        for (corner, normal) in corner_normals.into_iter().enumerate() {
+            let vertex = self.indices[corner] as usize;
+            let normal = match normal.map(Vec3::normalized) {
+                Some(normal) if normal.x.is_finite() && normal.y.is_finite() && normal.z.is_finite() => normal,
+                // Degenerate surroundings, the stored normal is as good a guess as any
+                _ => self.vertices[vertex].normal,
+            };
+
+            let existing = assigned[vertex].iter().find(|(assigned_normal, _)| assigned_normal.map(f32::to_bits) == normal.map(f32::to_bits));
+            self.indices[corner] = match existing {
+                Some(&(_, index)) => index,
+                None if assigned[vertex].is_empty() => {
+                    self.vertices[vertex].normal = normal;
+                    assigned[vertex].push((normal, vertex as u32));
+                    vertex as u32
+                }
+                None => {
+                    let index = self.vertices.len() as u32;
+                    let mut copy = self.vertices[vertex].clone();
+                    copy.normal = normal;
+                    self.vertices.push(copy);
+                    assigned[vertex].push((normal, index));
+                    index
+                }
+            };
+        }
+        // Splitting can push a mesh past the format's vertex limit, which validate and the writer will report
+        self.vertex_count = self.vertices.len() as u32;
+        Ok(())
+    }
+
+    pub fn normal_deviation_report(&self) -> NormalDeviationReport {
+        let mut report = NormalDeviationReport::default();
+        let mut total = 0.0;
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [Some(a), Some(b), Some(c)] = [triangle[0], triangle[1], triangle[2]].map(|i| self.vertices.get(i as usize)) else {
+                report.degenerate_triangles += 1;
+                report.per_triangle.push(None);
+                continue;
+            };
+            let face_normal = triangle_normal(a.position, b.position, c.position);
+            if face_normal.magnitude_squared() == 0.0 || !face_normal.magnitude_squared().is_finite() {
+                report.degenerate_triangles += 1;
+                report.per_triangle.push(None);
+                continue;
+            }
+
+            let deviation = [a, b, c]
+                .iter()
+                .filter(|vertex| vertex.normal.magnitude_squared() > 0.0)
+                .map(|vertex| angle_between(vertex.normal, face_normal))
+                .fold(None, |max: Option<f32>, angle| Some(max.map_or(angle, |max| max.max(angle))));
+
+            if let Some(deviation) = deviation {
+                report.max_deviation = report.max_deviation.max(deviation);
+                total += deviation;
+                if deviation > core::f32::consts::FRAC_PI_2 {
+                    report.flipped_triangles += 1;
+                }
+            }
+            report.per_triangle.push(deviation);
+        }
+
+        let measured = report.per_triangle.iter().flatten().count();
+        if measured > 0 {
+            report.mean_deviation = total / measured as f32;
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, primitive_sphere, PrimitiveStyle};
+
+    #[test]
+    fn flat_normals_match_faces() {
+        let mut sphere = primitive_sphere(1.0, 16, 8, PrimitiveStyle::default()).unwrap();
+        assert!(sphere.normal_deviation_report().max_deviation > 0.05);
+        sphere.recompute_normals(NormalMode::Flat).unwrap();
+        let report = sphere.normal_deviation_report();
+        assert!(report.max_deviation < 1e-3);
+        assert_eq!(report.flipped_triangles, 0);
+        assert!(sphere.validate().is_ok());
+    }
+
+    #[test]
+    fn crease_angle_keeps_hard_edges() {
+        let original = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        for mode in [NormalMode::AreaWeighted { crease_angle: 1.0 }, NormalMode::AngleWeighted { crease_angle: 1.0 }] {
+            let mut mesh = original.clone();
+            mesh.recompute_normals(mode).unwrap();
+            assert_eq!(mesh.vertices.len(), original.vertices.len());
+            assert!(mesh.normal_deviation_report().max_deviation < 1e-3);
+        }
+    }
+
+    #[test]
+    fn smoothing_across_every_edge() {
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        mesh.recompute_normals(NormalMode::AngleWeighted { crease_angle: core::f32::consts::PI }).unwrap();
+        // Every copy of a cube corner ends up with the same normal, pointing diagonally out
+        let center = mesh.vertices.iter().map(|vertex| vertex.position).sum::<Vec3<f32>>() / mesh.vertices.len() as f32;
+        for vertex in &mesh.vertices {
+            assert!(vertex.normal.distance((vertex.position - center).normalized()) < 1e-4);
+        }
+        assert_eq!(mesh.vertex_count as usize, mesh.vertices.len());
+    }
+
+    #[test]
+    fn report_finds_flipped_and_degenerate_triangles() {
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        let first = mesh.indices[0] as usize;
+        mesh.vertices[first].normal = -mesh.vertices[first].normal;
+        let last = mesh.indices.len() - 3;
+        let corner = mesh.indices[last];
+        mesh.indices[last..].fill(corner);
+
+        let report = mesh.normal_deviation_report();
+        assert!(report.flipped_triangles >= 1);
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.per_triangle.len(), mesh.indices.len() / 3);
+        assert_eq!(report.per_triangle[last / 3], None);
+    }
+
+    #[test]
+    fn out_of_range_indices_fail_without_changes() {
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        mesh.indices[4] = mesh.vertices.len() as u32;
+        let before = mesh.clone();
+        assert!(mesh.recompute_normals(NormalMode::Flat).is_err());
+        assert_eq!(mesh, before);
+
+        let report = mesh.normal_deviation_report();
+        assert_eq!(report.degenerate_triangles, 1);
+        assert_eq!(report.per_triangle[1], None);
+    }
+}
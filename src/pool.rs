@@ -0,0 +1,85 @@
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::io::Read;
+
+use crate::StormworksMesh;
+#[cfg(feature = "std")]
+use crate::{StormworksParseOptions, StormworksParserError};
+
+// Hands out meshes that were given back earlier, so loading lots of meshes over and over doesn't allocate for every one.
+// Not thread safe by itself, wrap it in a Mutex to share it.
+#[derive(Debug)]
+pub struct MeshPool {
+    free: Vec<StormworksMesh>,
+    // Meshes given back past this many are dropped instead of kept around
+    max_free: usize,
+}
+impl Default for MeshPool {
+    // Enough to recycle a typical batch of part meshes without holding on to much memory
+    fn default() -> Self {
+        MeshPool::new(MeshPool::DEFAULT_MAX_FREE)
+    }
+}
+impl MeshPool {
+    pub const DEFAULT_MAX_FREE: usize = 64;
+
+    pub fn new(max_free: usize) -> Self {
+        MeshPool { free: Vec::with_capacity(max_free), max_free }
+    }
+
+    // A recycled mesh if there is one, otherwise an empty one. Its contents are leftovers and meant to be overwritten.
+    pub fn take(&mut self) -> StormworksMesh {
+        self.free.pop().unwrap_or_default()
+    }
+
+    pub fn recycle(&mut self, mesh: StormworksMesh) {
+        if self.free.len() < self.max_free {
+            self.free.push(mesh);
+        }
+    }
+
+    pub fn free_count(&self) -> usize {
+        self.free.len()
+    }
+
+    #[cfg(feature = "std")]
+    pub fn load(&mut self, mesh_stream: impl Read) -> Result<StormworksMesh,StormworksParserError> {
+        self.load_with_options(mesh_stream, &StormworksParseOptions::default())
+    }
+    // Reads into a recycled mesh. On failure the mesh goes straight back into the pool.
+    #[cfg(feature = "std")]
+    pub fn load_with_options(&mut self, mesh_stream: impl Read, options: &StormworksParseOptions) -> Result<StormworksMesh,StormworksParserError> {
+        let mut mesh = self.take();
+        match mesh.read_into_with_options(mesh_stream, options) {
+            Ok(()) => Ok(mesh),
+            Err(err) => {
+                self.recycle(mesh);
+                Err(err)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_pool_recycles() {
+        let mut pool = MeshPool::default();
+        let mut mesh = pool.take();
+        mesh.indices.reserve(100);
+        pool.recycle(mesh);
+        assert_eq!(pool.free_count(), 1);
+        assert!(pool.take().indices.capacity() >= 100);
+    }
+
+    #[test]
+    fn drops_meshes_past_max_free() {
+        let mut pool = MeshPool::new(2);
+        for _ in 0..5 {
+            pool.recycle(StormworksMesh::default());
+        }
+        assert_eq!(pool.free_count(), 2);
+    }
+}
@@ -0,0 +1,366 @@
+use alloc::vec::Vec;
+use core::f32::consts::{PI, TAU};
+
+use vek::{vec::repr_c::vec2::Vec2, Rgba};
+
+use crate::{normals::triangle_normal, prelude::*, StormworksMesh, StormworksMeshBuilder, StormworksParserError, StormworksShaderType};
+
+// Edge length of one block in the game's building grid, in meters
+pub const BLOCK_SIZE: f32 = 0.25;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PrimitiveStyle {
+    pub color: Rgba<u8>,
+    pub shader: StormworksShaderType,
+}
+
+impl Default for PrimitiveStyle {
+    fn default() -> Self {
+        PrimitiveStyle { color: Rgba::new(255, 255, 255, 255), shader: StormworksShaderType::Opaque }
+    }
+}
+
+// Keeps positions and normals around so every triangle can be wound the way the game expects
+struct Shape {
+    builder: StormworksMeshBuilder,
+    color: Rgba<u8>,
+    positions: Vec<Vec3<f32>>,
+    normals: Vec<Vec3<f32>>,
+}
+
+impl Shape {
+    fn new(name: &str, style: PrimitiveStyle) -> Self {
+        let mut builder = StormworksMeshBuilder::new();
+        builder.begin_sub_mesh(name, style.shader);
+        Shape { builder, color: style.color, positions: Vec::new(), normals: Vec::new() }
+    }
+
+    fn vertex(&mut self, position: Vec3<f32>, normal: Vec3<f32>) -> u32 {
+        self.positions.push(position);
+        self.normals.push(normal);
+        self.builder.add_vertex(position, self.color, normal)
+    }
+
+    // Flips the order when it would face inwards according to the vertex normals, see normals::triangle_normal for the winding
+    fn triangle(&mut self, a: u32, b: u32, c: u32) {
+        let [pa, pb, pc] = [a, b, c].map(|i| self.positions[i as usize]);
+        let outward = self.normals[a as usize] + self.normals[b as usize] + self.normals[c as usize];
+        if triangle_normal(pa, pb, pc).dot(outward) < 0.0 {
+            self.builder.add_triangle(a, c, b);
+        } else {
+            self.builder.add_triangle(a, b, c);
+        }
+    }
+
+    // Convex and flat, fanned from the first point
+    fn flat_polygon(&mut self, points: &[Vec3<f32>], normal: Vec3<f32>) {
+        let first = self.vertex(points[0], normal);
+        let mut previous = self.vertex(points[1], normal);
+        for &point in &points[2..] {
+            let next = self.vertex(point, normal);
+            self.triangle(first, previous, next);
+            previous = next;
+        }
+    }
+
+    fn finish(self) -> Result<StormworksMesh, StormworksParserError> {
+        self.builder.build()
+    }
+}
+
+// Blocks are centered on the origin like in the game, so a shape n blocks long spans from -BLOCK_SIZE / 2 to (n - 0.5) * BLOCK_SIZE
+fn block_bounds(size_blocks: Vec3<u32>) -> (Vec3<f32>, Vec3<f32>) {
+    let min = Vec3::broadcast(-BLOCK_SIZE / 2.0);
+    (min, min + size_blocks.map(|n| n as f32 * BLOCK_SIZE))
+}
+
+fn unit_circle(segments: u32, i: u32) -> Vec2<f32> {
+    let angle = i as f32 / segments as f32 * TAU;
+    Vec2::new(angle.cos(), angle.sin())
+}
+
+pub fn primitive_box(size_blocks: Vec3<u32>, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let (min, max) = block_bounds(size_blocks);
+    let corner = |x: bool, y: bool, z: bool| Vec3::new(if x { max.x } else { min.x }, if y { max.y } else { min.y }, if z { max.z } else { min.z });
+
+    let mut shape = Shape::new("box", style);
+    shape.flat_polygon(&[corner(true, false, false), corner(true, true, false), corner(true, true, true), corner(true, false, true)], Vec3::unit_x());
+    shape.flat_polygon(&[corner(false, false, false), corner(false, false, true), corner(false, true, true), corner(false, true, false)], -Vec3::unit_x());
+    shape.flat_polygon(&[corner(false, true, false), corner(false, true, true), corner(true, true, true), corner(true, true, false)], Vec3::unit_y());
+    shape.flat_polygon(&[corner(false, false, false), corner(true, false, false), corner(true, false, true), corner(false, false, true)], -Vec3::unit_y());
+    shape.flat_polygon(&[corner(false, false, true), corner(true, false, true), corner(true, true, true), corner(false, true, true)], Vec3::unit_z());
+    shape.flat_polygon(&[corner(false, false, false), corner(false, true, false), corner(true, true, false), corner(true, false, false)], -Vec3::unit_z());
+    shape.finish()
+}
+
+// Full height along the -Z face, sloping down to the bottom edge at +Z
+pub fn primitive_wedge(size_blocks: Vec3<u32>, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let (min, max) = block_bounds(size_blocks);
+    let (bottom_back_left, bottom_back_right) = (Vec3::new(min.x, min.y, min.z), Vec3::new(max.x, min.y, min.z));
+    let (top_back_left, top_back_right) = (Vec3::new(min.x, max.y, min.z), Vec3::new(max.x, max.y, min.z));
+    let (bottom_front_left, bottom_front_right) = (Vec3::new(min.x, min.y, max.z), Vec3::new(max.x, min.y, max.z));
+    let slope_normal = Vec3::new(0.0, max.z - min.z, max.y - min.y).normalized();
+
+    let mut shape = Shape::new("wedge", style);
+    shape.flat_polygon(&[bottom_back_left, bottom_back_right, bottom_front_right, bottom_front_left], -Vec3::unit_y());
+    shape.flat_polygon(&[bottom_back_left, top_back_left, top_back_right, bottom_back_right], -Vec3::unit_z());
+    shape.flat_polygon(&[top_back_left, bottom_front_left, bottom_front_right, top_back_right], slope_normal);
+    shape.flat_polygon(&[bottom_back_left, bottom_front_left, top_back_left], -Vec3::unit_x());
+    shape.flat_polygon(&[bottom_back_right, top_back_right, bottom_front_right], Vec3::unit_x());
+    shape.finish()
+}
+
+// Square base on the bottom of the blocks, apex centered on the top
+pub fn primitive_pyramid(size_blocks: Vec3<u32>, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let (min, max) = block_bounds(size_blocks);
+    let apex = Vec3::new((min.x + max.x) / 2.0, max.y, (min.z + max.z) / 2.0);
+    let base = [
+        Vec3::new(min.x, min.y, min.z),
+        Vec3::new(max.x, min.y, min.z),
+        Vec3::new(max.x, min.y, max.z),
+        Vec3::new(min.x, min.y, max.z),
+    ];
+    let centroid = Vec3::new(apex.x, (3.0 * min.y + max.y) / 4.0, apex.z);
+
+    let mut shape = Shape::new("pyramid", style);
+    shape.flat_polygon(&base, -Vec3::unit_y());
+    for i in 0..4 {
+        let (a, b) = (base[i], base[(i + 1) % 4]);
+        let mut normal = (b - a).cross(apex - a).normalized();
+        // Pointing away from the inside, whichever way the cross product came out
+        if normal.dot(a - centroid) < 0.0 {
+            normal = -normal;
+        }
+        shape.flat_polygon(&[a, b, apex], normal);
+    }
+    shape.finish()
+}
+
+// Round shapes stand on the origin with Y as their axis. Segment and ring counts below 3 and 2 are raised to those.
+pub fn primitive_cylinder(radius: f32, height: f32, segments: u32, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let segments = segments.max(3);
+    let mut shape = Shape::new("cylinder", style);
+
+    let mut ring = Vec::with_capacity(segments as usize);
+    for i in 0..segments {
+        let direction = unit_circle(segments, i);
+        let normal = Vec3::new(direction.x, 0.0, direction.y);
+        let bottom = shape.vertex(Vec3::new(direction.x * radius, 0.0, direction.y * radius), normal);
+        let top = shape.vertex(Vec3::new(direction.x * radius, height, direction.y * radius), normal);
+        ring.push((bottom, top));
+    }
+    for i in 0..ring.len() {
+        let ((bottom_a, top_a), (bottom_b, top_b)) = (ring[i], ring[(i + 1) % ring.len()]);
+        shape.triangle(bottom_a, top_a, top_b);
+        shape.triangle(bottom_a, top_b, bottom_b);
+    }
+
+    let circle: Vec<Vec2<f32>> = (0..segments).map(|i| unit_circle(segments, i) * radius).collect();
+    shape.flat_polygon(&circle.iter().map(|p| Vec3::new(p.x, height, p.y)).collect::<Vec<_>>(), Vec3::unit_y());
+    shape.flat_polygon(&circle.iter().map(|p| Vec3::new(p.x, 0.0, p.y)).collect::<Vec<_>>(), -Vec3::unit_y());
+    shape.finish()
+}
+
+pub fn primitive_cone(radius: f32, height: f32, segments: u32, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let segments = segments.max(3);
+    let mut shape = Shape::new("cone", style);
+    let slope_normal = |direction: Vec2<f32>| Vec3::new(direction.x * height, radius, direction.y * height).normalized();
+
+    for i in 0..segments {
+        let (direction_a, direction_b) = (unit_circle(segments, i), unit_circle(segments, (i + 1) % segments));
+        let a = shape.vertex(Vec3::new(direction_a.x * radius, 0.0, direction_a.y * radius), slope_normal(direction_a));
+        let b = shape.vertex(Vec3::new(direction_b.x * radius, 0.0, direction_b.y * radius), slope_normal(direction_b));
+        // The apex gets its own vertex per segment, so its normal can follow the middle of that segment
+        let middle = unit_circle(segments * 2, i * 2 + 1);
+        let apex = shape.vertex(Vec3::new(0.0, height, 0.0), slope_normal(middle));
+        shape.triangle(a, b, apex);
+    }
+
+    let base: Vec<Vec3<f32>> = (0..segments).map(|i| unit_circle(segments, i) * radius).map(|p| Vec3::new(p.x, 0.0, p.y)).collect();
+    shape.flat_polygon(&base, -Vec3::unit_y());
+    shape.finish()
+}
+
+// Centered on the origin, rings are the divisions from pole to pole
+pub fn primitive_sphere(radius: f32, segments: u32, rings: u32, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let (segments, rings) = (segments.max(3), rings.max(2));
+    let mut shape = Shape::new("sphere", style);
+
+    let top = shape.vertex(Vec3::new(0.0, radius, 0.0), Vec3::unit_y());
+    let bottom = shape.vertex(Vec3::new(0.0, -radius, 0.0), -Vec3::unit_y());
+    let mut latitudes: Vec<Vec<u32>> = Vec::with_capacity(rings as usize - 1);
+    for ring in 1..rings {
+        let polar = ring as f32 / rings as f32 * PI;
+        let (sin, cos) = (polar.sin(), polar.cos());
+        let latitude = (0..segments)
+            .map(|i| {
+                let direction = unit_circle(segments, i);
+                let normal = Vec3::new(direction.x * sin, cos, direction.y * sin);
+                shape.vertex(normal * radius, normal)
+            })
+            .collect();
+        latitudes.push(latitude);
+    }
+
+    let segments = segments as usize;
+    for i in 0..segments {
+        let next = (i + 1) % segments;
+        shape.triangle(top, latitudes[0][i], latitudes[0][next]);
+        for pair in latitudes.windows(2) {
+            let (upper, lower) = (&pair[0], &pair[1]);
+            shape.triangle(upper[i], lower[i], lower[next]);
+            shape.triangle(upper[i], lower[next], upper[next]);
+        }
+        let last = &latitudes[latitudes.len() - 1];
+        shape.triangle(bottom, last[next], last[i]);
+    }
+    shape.finish()
+}
+
+fn signed_area_2(points: &[Vec2<f32>]) -> f32 {
+    (0..points.len())
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum()
+}
+
+fn point_in_triangle(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>, c: Vec2<f32>) -> bool {
+    let side = |from: Vec2<f32>, to: Vec2<f32>| (to.x - from.x) * (p.y - from.y) - (to.y - from.y) * (p.x - from.x);
+    let (ab, bc, ca) = (side(a, b), side(b, c), side(c, a));
+    (ab >= 0.0 && bc >= 0.0 && ca >= 0.0) || (ab <= 0.0 && bc <= 0.0 && ca <= 0.0)
+}
+
+// Ear clipping, so concave outlines work too as long as they don't cross themselves
+fn triangulate_polygon(points: &[Vec2<f32>]) -> Vec<[usize;3]> {
+    let orientation = signed_area_2(points).signum();
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&i| {
+            let (a, b, c) = (remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]);
+            let (pa, pb, pc) = (points[a], points[b], points[c]);
+            let convex = ((pb.x - pa.x) * (pc.y - pa.y) - (pb.y - pa.y) * (pc.x - pa.x)) * orientation > 0.0;
+            convex && !remaining.iter().any(|&other| other != a && other != b && other != c && point_in_triangle(points[other], pa, pb, pc))
+        });
+        // Only self intersecting or degenerate outlines run out of ears, clip whatever is left rather than looping forever
+        let i = ear.unwrap_or(0);
+        triangles.push([remaining[(i + count - 1) % count], remaining[i], remaining[(i + 1) % count]]);
+        remaining.remove(i);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+// The outline lies in the XZ plane and is extruded upwards along Y. Outlines with fewer than 3 points give an empty mesh.
+pub fn primitive_extruded_polygon(outline: &[Vec2<f32>], height: f32, style: PrimitiveStyle) -> Result<StormworksMesh, StormworksParserError> {
+    let mut shape = Shape::new("extrusion", style);
+    if outline.len() < 3 {
+        return shape.finish();
+    }
+
+    let orientation = signed_area_2(outline).signum();
+    for i in 0..outline.len() {
+        let (a, b) = (outline[i], outline[(i + 1) % outline.len()]);
+        let edge = b - a;
+        // The interior is to the left of each edge for counter clockwise outlines, to the right otherwise
+        let normal = Vec3::new(edge.y, 0.0, -edge.x).normalized() * orientation;
+        shape.flat_polygon(
+            &[Vec3::new(a.x, 0.0, a.y), Vec3::new(b.x, 0.0, b.y), Vec3::new(b.x, height, b.y), Vec3::new(a.x, height, a.y)],
+            normal,
+        );
+    }
+
+    for (y, normal) in [(height, Vec3::unit_y()), (0.0, -Vec3::unit_y())] {
+        let cap: Vec<u32> = outline.iter().map(|p| shape.vertex(Vec3::new(p.x, y, p.y), normal)).collect();
+        for [a, b, c] in triangulate_polygon(outline) {
+            shape.triangle(cap[a], cap[b], cap[c]);
+        }
+    }
+    shape.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Volume by the divergence theorem, summed from the given point. Only a closed mesh gives the same answer from everywhere.
+    fn volume_from(mesh: &StormworksMesh, origin: Vec3<f32>) -> f32 {
+        mesh.indices.chunks_exact(3).map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize].position - origin);
+            a.dot(c.cross(b)) / 6.0
+        }).sum()
+    }
+
+    // Closed, outward facing and with the expected volume. Returns the largest angle between a stored normal and its face.
+    fn check(mesh: &StormworksMesh, volume: f32, tolerance: f32) -> f32 {
+        mesh.validate().unwrap();
+        let measured = volume_from(mesh, Vec3::zero());
+        assert!((measured - volume).abs() <= volume * tolerance, "volume {measured} != {volume}");
+        assert!((volume_from(mesh, Vec3::new(1.0, -2.0, 3.0)) - measured).abs() <= volume * 1e-4, "not closed");
+
+        let mut max_deviation: f32 = 0.0;
+        for triangle in mesh.indices.chunks_exact(3) {
+            let corners = [0, 1, 2].map(|k| &mesh.vertices[triangle[k] as usize]);
+            let face = triangle_normal(corners[0].position, corners[1].position, corners[2].position);
+            assert!(face.magnitude() > 0.0, "degenerate triangle");
+            for corner in corners {
+                let cos = face.normalized().dot(corner.normal.normalized());
+                assert!(cos > 0.0, "flipped triangle");
+                max_deviation = max_deviation.max(cos.min(1.0).acos());
+            }
+        }
+        max_deviation
+    }
+
+    #[test]
+    fn block_shapes() {
+        let size = Vec3::new(2, 3, 4);
+        let box_volume = 2.0 * 3.0 * 4.0 * BLOCK_SIZE * BLOCK_SIZE * BLOCK_SIZE;
+        for (mesh, volume) in [
+            (primitive_box(size, PrimitiveStyle::default()), box_volume),
+            (primitive_wedge(size, PrimitiveStyle::default()), box_volume / 2.0),
+            (primitive_pyramid(size, PrimitiveStyle::default()), box_volume / 3.0),
+        ] {
+            // Flat faces, so every normal is exactly its face's
+            assert!(check(&mesh.unwrap(), volume, 1e-5) < 1e-3);
+        }
+    }
+
+    #[test]
+    fn round_shapes() {
+        let segments = 64;
+        // Area of the inscribed polygon the round shapes are built on
+        let polygon_area = segments as f32 / 2.0 * (TAU / segments as f32).sin();
+        check(&primitive_cylinder(1.0, 2.0, segments, PrimitiveStyle::default()).unwrap(), polygon_area * 2.0, 1e-4);
+        check(&primitive_cone(1.0, 2.0, segments, PrimitiveStyle::default()).unwrap(), polygon_area * 2.0 / 3.0, 1e-4);
+        // Faceted, so a little smaller than the real sphere
+        check(&primitive_sphere(1.0, segments, 32, PrimitiveStyle::default()).unwrap(), 4.0 / 3.0 * PI, 0.01);
+    }
+
+    #[test]
+    fn extruded_concave_outline() {
+        // An L shape, 3 square meters, which needs real ear clipping rather than a fan
+        let outline = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)].map(|(x, y)| Vec2::new(x, y));
+        let mesh = primitive_extruded_polygon(&outline, 0.5, PrimitiveStyle::default()).unwrap();
+        assert!(check(&mesh, 1.5, 1e-5) < 1e-3);
+
+        let mut reversed = outline;
+        reversed.reverse();
+        let mesh = primitive_extruded_polygon(&reversed, 0.5, PrimitiveStyle::default()).unwrap();
+        assert!(check(&mesh, 1.5, 1e-5) < 1e-3);
+    }
+
+    #[test]
+    fn style_is_applied() {
+        let style = PrimitiveStyle { color: Rgba::new(10, 20, 30, 40), shader: StormworksShaderType::Emissive };
+        let mesh = primitive_box(Vec3::one(), style).unwrap();
+        assert!(mesh.vertices.iter().all(|vertex| vertex.color == style.color));
+        assert!(mesh.sub_meshes.iter().all(|sub_mesh| sub_mesh.shader_id == style.shader));
+    }
+}
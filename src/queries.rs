@@ -0,0 +1,320 @@
+use alloc::vec::Vec;
+
+use vek::vec::repr_c::vec3::Vec3;
+
+use crate::{
+    bvh::{ray_aabb, ray_triangle, triangle_positions},
+    normals::triangle_normal,
+    Aabb, MeshBvh, StormworksMesh,
+};
+
+// Irregular so they don't line up with the block grid, where rays would run along edges
+const CONTAINS_DIRECTIONS: [Vec3<f32>;3] = [
+    Vec3 { x: 0.5377, y: 0.8311, z: 0.1419 },
+    Vec3 { x: -0.3106, y: 0.2727, z: 0.9106 },
+    Vec3 { x: 0.7593, y: -0.5921, z: -0.2699 },
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestPoint {
+    pub position: Vec3<f32>,
+    pub distance: f32,
+    pub triangle: u32,
+    // Weights of the triangle's three corners, in index order
+    pub barycentrics: Vec3<f32>,
+    pub sub_mesh: Option<u32>,
+}
+
+fn point_aabb_distance_squared(point: Vec3<f32>, aabb: &Aabb) -> f32 {
+    let outside = Vec3::partial_max(Vec3::partial_max(aabb.min - point, point - aabb.max), Vec3::zero());
+    outside.magnitude_squared()
+}
+
+// Ericson, Real-Time Collision Detection 5.1.5. Returns the point and its barycentrics.
+pub(crate) fn closest_point_on_triangle(p: Vec3<f32>, [a, b, c]: [Vec3<f32>;3]) -> (Vec3<f32>, Vec3<f32>) {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+    let (d1, d2) = (ab.dot(ap), ac.dot(ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return (a, Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(bp), ac.dot(bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return (b, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        let v = d1 / (d1 - d3);
+        return (a + ab * v, Vec3::new(1.0 - v, v, 0.0));
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(cp), ac.dot(cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return (c, Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        let w = d2 / (d2 - d6);
+        return (a + ac * w, Vec3::new(1.0 - w, 0.0, w));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        let w = (d4 - d3) / ((d4 - d3) + (d5 - d6));
+        return (b + (c - b) * w, Vec3::new(0.0, 1.0 - w, w));
+    }
+
+    let denominator = 1.0 / (va + vb + vc);
+    let (v, w) = (vb * denominator, vc * denominator);
+    if !v.is_finite() || !w.is_finite() {
+        // Degenerate triangle, one of its corners is as good an answer as any
+        return (a, Vec3::new(1.0, 0.0, 0.0));
+    }
+    (a + ab * v + ac * w, Vec3::new(1.0 - v - w, v, w))
+}
+
+// Ericson 5.1.9, squared distance between segments p1 q1 and p2 q2
+pub(crate) fn segment_segment_distance_squared(p1: Vec3<f32>, q1: Vec3<f32>, p2: Vec3<f32>, q2: Vec3<f32>) -> f32 {
+    let (d1, d2, r) = (q1 - p1, q2 - p2, p1 - p2);
+    let (a, e, f) = (d1.magnitude_squared(), d2.magnitude_squared(), d2.dot(r));
+
+    let (s, t) = if a <= f32::EPSILON && e <= f32::EPSILON {
+        (0.0, 0.0)
+    } else if a <= f32::EPSILON {
+        (0.0, (f / e).clamp(0.0, 1.0))
+    } else {
+        let c = d1.dot(r);
+        if e <= f32::EPSILON {
+            ((-c / a).clamp(0.0, 1.0), 0.0)
+        } else {
+            let b = d1.dot(d2);
+            let denominator = a * e - b * b;
+            let mut s = if denominator != 0.0 { ((b * f - c * e) / denominator).clamp(0.0, 1.0) } else { 0.0 };
+            let mut t = (b * s + f) / e;
+            if t < 0.0 {
+                t = 0.0;
+                s = (-c / a).clamp(0.0, 1.0);
+            } else if t > 1.0 {
+                t = 1.0;
+                s = ((b - c) / a).clamp(0.0, 1.0);
+            }
+            (s, t)
+        }
+    };
+    (p1 + d1 * s).distance_squared(p2 + d2 * t)
+}
+
+pub(crate) fn segment_triangle_distance_squared(p: Vec3<f32>, q: Vec3<f32>, triangle: [Vec3<f32>;3]) -> f32 {
+    // The direction isn't normalized, so the hit distance is a fraction of the segment
+    if ray_triangle(p, q - p, triangle).is_some_and(|(t, _, _)| t <= 1.0) {
+        return 0.0;
+    }
+    let [a, b, c] = triangle;
+    [
+        closest_point_on_triangle(p, triangle).0.distance_squared(p),
+        closest_point_on_triangle(q, triangle).0.distance_squared(q),
+        segment_segment_distance_squared(p, q, a, b),
+        segment_segment_distance_squared(p, q, b, c),
+        segment_segment_distance_squared(p, q, c, a),
+    ]
+    .into_iter()
+    .fold(f32::INFINITY, f32::min)
+}
+
+impl MeshBvh {
+    // Whether any triangle passes triangle_test, only looking inside nodes that pass node_test
+    pub(crate) fn any_triangle(&self, node_test: impl Fn(&Aabb) -> bool, mut triangle_test: impl FnMut(u32) -> bool) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0u32);
+        while let Some(index) = stack.pop() {
+            let node = self.nodes[index as usize];
+            if !node_test(&node.aabb) {
+                continue;
+            }
+            if node.count > 0 {
+                if self.triangles[node.start as usize..(node.start + node.count) as usize].iter().any(|&triangle| triangle_test(triangle)) {
+                    return true;
+                }
+            } else {
+                stack.push(node.start);
+                stack.push(index + 1);
+            }
+        }
+        false
+    }
+
+    // None if the BVH has no triangles
+    pub fn closest_point(&self, mesh: &StormworksMesh, point: Vec3<f32>) -> Option<ClosestPoint> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let mut best: Option<(f32, u32, Vec3<f32>, Vec3<f32>)> = None;
+        let mut stack = Vec::with_capacity(64);
+        stack.push((0u32, point_aabb_distance_squared(point, &self.nodes[0].aabb)));
+        while let Some((index, node_distance)) = stack.pop() {
+            if best.is_some_and(|(best_distance, _, _, _)| node_distance > best_distance) {
+                continue;
+            }
+            let node = self.nodes[index as usize];
+            if node.count > 0 {
+                for &triangle in &self.triangles[node.start as usize..(node.start + node.count) as usize] {
+                    let (position, barycentrics) = closest_point_on_triangle(point, triangle_positions(mesh, triangle));
+                    let distance = position.distance_squared(point);
+                    if best.is_none_or(|(best_distance, _, _, _)| distance < best_distance) {
+                        best = Some((distance, triangle, position, barycentrics));
+                    }
+                }
+                continue;
+            }
+
+            // Nearer child on top of the stack
+            let (left, right) = (index + 1, node.start);
+            let (left_distance, right_distance) = (
+                point_aabb_distance_squared(point, &self.nodes[left as usize].aabb),
+                point_aabb_distance_squared(point, &self.nodes[right as usize].aabb),
+            );
+            if left_distance < right_distance {
+                stack.push((right, right_distance));
+                stack.push((left, left_distance));
+            } else {
+                stack.push((left, left_distance));
+                stack.push((right, right_distance));
+            }
+        }
+
+        let (_, triangle, position, barycentrics) = best?;
+        Some(ClosestPoint { position, distance: position.distance(point), triangle, barycentrics, sub_mesh: self.sub_mesh_of(triangle) })
+    }
+
+    // Unsigned distance to the surface, infinite if the BVH has no triangles
+    pub fn distance(&self, mesh: &StormworksMesh, point: Vec3<f32>) -> f32 {
+        self.closest_point(mesh, point).map_or(f32::INFINITY, |closest| closest.distance)
+    }
+
+    // Only meaningful for closed meshes with outward facing triangles, like the game's own.
+    // Counts signed crossings (the winding number) along three rays and goes with the majority,
+    // so a ray grazing an edge or vertex can't flip the answer on its own.
+    pub fn contains(&self, mesh: &StormworksMesh, point: Vec3<f32>) -> bool {
+        let votes = CONTAINS_DIRECTIONS
+            .iter()
+            .filter(|&&direction| {
+                let mut winding = 0i32;
+                self.traverse_ray(mesh, point, direction, f32::INFINITY, |triangle, _, _, _| {
+                    let [a, b, c] = triangle_positions(mesh, triangle);
+                    let facing = triangle_normal(a, b, c).dot(direction);
+                    // Leaving through a triangle's front means the ray started behind it, on the inside
+                    if facing > 0.0 {
+                        winding += 1;
+                    } else if facing < 0.0 {
+                        winding -= 1;
+                    }
+                    Some(f32::INFINITY)
+                });
+                winding > 0
+            })
+            .count();
+        votes >= 2
+    }
+
+    // Whether the sphere touches the surface. For a solid test, also check contains(center).
+    pub fn intersects_sphere(&self, mesh: &StormworksMesh, center: Vec3<f32>, radius: f32) -> bool {
+        let radius_squared = radius * radius;
+        self.any_triangle(
+            |aabb| point_aabb_distance_squared(center, aabb) <= radius_squared,
+            |triangle| closest_point_on_triangle(center, triangle_positions(mesh, triangle)).0.distance_squared(center) <= radius_squared,
+        )
+    }
+
+    // Whether the capsule around segment a b touches the surface. For a solid test, also check contains(a).
+    pub fn intersects_capsule(&self, mesh: &StormworksMesh, a: Vec3<f32>, b: Vec3<f32>, radius: f32) -> bool {
+        let radius_squared = radius * radius;
+        let inverse_direction = (b - a).map(|d| 1.0 / d);
+        self.any_triangle(
+            |aabb| {
+                let grown = Aabb { min: aabb.min - radius, max: aabb.max + radius };
+                ray_aabb(&grown, a, inverse_direction, 1.0).is_some()
+            },
+            |triangle| segment_triangle_distance_squared(a, b, triangle_positions(mesh, triangle)) <= radius_squared,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, MeshAnchor, PrimitiveStyle};
+
+    // Unit cube centered on the origin
+    fn unit_cube() -> (StormworksMesh, MeshBvh) {
+        let mut mesh = primitive_box(Vec3::new(4, 4, 4), PrimitiveStyle::default()).unwrap();
+        mesh.center_on(MeshAnchor::BoundsCenter);
+        let bvh = mesh.build_bvh();
+        (mesh, bvh)
+    }
+
+    fn assert_close(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!(a.distance(b) < 1e-5, "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn closest_point_on_a_unit_cube() {
+        let (mesh, bvh) = unit_cube();
+
+        // Straight out from a face, from an edge and from a corner
+        let closest = bvh.closest_point(&mesh, Vec3::new(0.1, 0.2, 2.0)).unwrap();
+        assert_close(closest.position, Vec3::new(0.1, 0.2, 0.5));
+        assert!((closest.distance - 1.5).abs() < 1e-5);
+        assert_eq!(closest.sub_mesh, Some(0));
+        let [a, b, c] = triangle_positions(&mesh, closest.triangle);
+        assert_close(a * closest.barycentrics.x + b * closest.barycentrics.y + c * closest.barycentrics.z, closest.position);
+
+        assert_close(bvh.closest_point(&mesh, Vec3::new(1.5, 1.5, 0.0)).unwrap().position, Vec3::new(0.5, 0.5, 0.0));
+        assert_close(bvh.closest_point(&mesh, Vec3::new(-2.0, 3.0, -4.0)).unwrap().position, Vec3::new(-0.5, 0.5, -0.5));
+
+        // From the inside the nearest wall wins
+        let closest = bvh.closest_point(&mesh, Vec3::new(0.0, -0.4, 0.0)).unwrap();
+        assert_close(closest.position, Vec3::new(0.0, -0.5, 0.0));
+        assert!((bvh.distance(&mesh, Vec3::new(0.0, -0.4, 0.0)) - 0.1).abs() < 1e-5);
+    }
+
+    #[test]
+    fn contains_on_a_unit_cube() {
+        let (mesh, bvh) = unit_cube();
+        assert!(bvh.contains(&mesh, Vec3::zero()));
+        assert!(bvh.contains(&mesh, Vec3::new(0.49, -0.49, 0.3)));
+        assert!(!bvh.contains(&mesh, Vec3::new(0.51, 0.0, 0.0)));
+        assert!(!bvh.contains(&mesh, Vec3::new(3.0, -2.0, 1.0)));
+    }
+
+    #[test]
+    fn sphere_and_capsule_overlaps() {
+        let (mesh, bvh) = unit_cube();
+        assert!(bvh.intersects_sphere(&mesh, Vec3::new(0.0, 0.0, 0.9), 0.5));
+        assert!(!bvh.intersects_sphere(&mesh, Vec3::new(0.0, 0.0, 1.1), 0.5));
+        // A sphere inside doesn't touch the surface
+        assert!(!bvh.intersects_sphere(&mesh, Vec3::zero(), 0.2));
+
+        assert!(bvh.intersects_capsule(&mesh, Vec3::new(-2.0, 0.0, 0.7), Vec3::new(2.0, 0.0, 0.7), 0.3));
+        assert!(!bvh.intersects_capsule(&mesh, Vec3::new(-2.0, 0.0, 0.9), Vec3::new(2.0, 0.0, 0.9), 0.3));
+        // A segment passing straight through counts even with no radius
+        assert!(bvh.intersects_capsule(&mesh, Vec3::new(0.0, -2.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 0.0));
+    }
+
+    #[test]
+    fn empty_bvh() {
+        let mesh = StormworksMesh::default();
+        let bvh = mesh.build_bvh();
+        assert!(bvh.closest_point(&mesh, Vec3::zero()).is_none());
+        assert_eq!(bvh.distance(&mesh, Vec3::zero()), f32::INFINITY);
+        assert!(!bvh.contains(&mesh, Vec3::zero()));
+        assert!(!bvh.intersects_sphere(&mesh, Vec3::zero(), 1.0));
+    }
+}
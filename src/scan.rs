@@ -0,0 +1,136 @@
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::{build_sub_meshes, detect_format_version, read_u16_from, read_u32_from, MeshFormatVersion, MeshSizeMismatch, StormworksParseOptions, StormworksParserError, StormworksSubMesh};
+
+// Everything in a .mesh except the vertex and index data
+#[derive(Clone, Debug, PartialEq)]
+pub struct StormworksMeshHeader {
+    pub header0: u16,
+    pub header1: u16,
+    pub header3: u16,
+    pub header4: u16,
+    pub version: MeshFormatVersion,
+    pub vertex_count: u32,
+    pub index_count: u32,
+    pub sub_mesh_count: u32,
+    // Sub meshes are fully read, names, shaders and stored bounds included
+    pub sub_meshes: Vec<StormworksSubMesh>,
+    // Bytes from where the mesh started to the end of the stream
+    pub file_length: u64,
+    // Bytes left in the stream after the last sub mesh, normally none
+    pub trailing_bytes: u64,
+}
+
+pub fn scan_mesh_header<R: Read + Seek>(mesh_stream: &mut R) -> Result<StormworksMeshHeader,StormworksParserError> {
+    scan_mesh_header_with_options(mesh_stream, &StormworksParseOptions::default())
+}
+
+// Reads the headers and sub meshes, seeking over the vertex and index blocks instead of decoding them.
+// Since those blocks are skipped their sizes are checked against the stream length, so a truncated file is still caught.
+pub fn scan_mesh_header_with_options<R: Read + Seek>(mesh_stream: &mut R, options: &StormworksParseOptions) -> Result<StormworksMeshHeader,StormworksParserError> {
+    let start = mesh_stream.stream_position()?;
+    let file_length = mesh_stream.seek(SeekFrom::End(0))? - start;
+    mesh_stream.seek(SeekFrom::Start(start))?;
+
+    let mut filetypemarker: [u8;4] = [0;4];
+    mesh_stream.read_exact(&mut filetypemarker)?;
+    if filetypemarker != *b"mesh" {
+        return Err(StormworksParserError::NotMesh);
+    }
+
+    let header0 = read_u16_from(mesh_stream)?;
+    let header1 = read_u16_from(mesh_stream)?;
+    let vertex_count = read_u16_from(mesh_stream)? as u32;
+    let header3 = read_u16_from(mesh_stream)?;
+    let header4 = read_u16_from(mesh_stream)?;
+
+    let version = detect_format_version([header0, header1, header3, header4], options)?;
+
+    // Seeking past the end doesn't fail, so check the block fits before skipping it
+    let skip_checked = |mesh_stream: &mut R, length: u64| -> Result<(),StormworksParserError> {
+        let end = mesh_stream.stream_position()? - start + length;
+        if end > file_length {
+            return Err(MeshSizeMismatch { expected_at_least: end, file_length }.into());
+        }
+        mesh_stream.seek(SeekFrom::Current(length as i64))?;
+        Ok(())
+    };
+
+    skip_checked(mesh_stream, vertex_count as u64 * version.bytes_per_vertex() as u64)?;
+
+    let index_count = read_u32_from(mesh_stream)?;
+    skip_checked(mesh_stream, index_count as u64 * 2)?;
+
+    let sub_mesh_count = read_u16_from(mesh_stream)? as u32;
+    let sub_meshes = build_sub_meshes(mesh_stream, sub_mesh_count, index_count, options)?;
+
+    let trailing_bytes = file_length - (mesh_stream.stream_position()? - start);
+
+    Ok(StormworksMeshHeader {
+        header0,
+        header1,
+        header3,
+        header4,
+        version,
+        vertex_count,
+        index_count,
+        sub_mesh_count,
+        sub_meshes,
+        file_length,
+        trailing_bytes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use vek::{mat::repr_c::mat4::Mat4, vec::repr_c::vec3::Vec3};
+    use crate::{parse_stormworks_mesh, primitive_box, primitive_sphere, write_stormworks_mesh, PrimitiveStyle, StormworksMesh, StormworksShaderType};
+
+    fn fixture() -> Vec<u8> {
+        let emissive = PrimitiveStyle { shader: StormworksShaderType::Emissive, ..Default::default() };
+        let inputs = [
+            (primitive_box(Vec3::new(2, 3, 4), PrimitiveStyle::default()).unwrap(), Mat4::identity()),
+            (primitive_sphere(0.5, 8, 4, emissive).unwrap(), Mat4::translation_3d(Vec3::new(2.0, 0.0, 0.0))),
+        ];
+        let mut bytes = Vec::new();
+        write_stormworks_mesh(&StormworksMesh::merge(&inputs).unwrap().remove(0), &mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn matches_a_full_parse() {
+        let mut bytes = fixture();
+        let mesh = parse_stormworks_mesh(&bytes).unwrap();
+        let header = scan_mesh_header(&mut Cursor::new(&bytes)).unwrap();
+
+        assert_eq!([header.header0, header.header1, header.header3, header.header4], [mesh.header0, mesh.header1, mesh.header3, mesh.header4]);
+        assert_eq!(header.version, MeshFormatVersion::V7);
+        assert_eq!((header.vertex_count, header.index_count, header.sub_mesh_count), (mesh.vertex_count, mesh.index_count, mesh.sub_mesh_count));
+        // Names and stored bounds included
+        assert_eq!(header.sub_meshes, mesh.sub_meshes);
+        assert_eq!(header.sub_meshes.len(), 2);
+        assert_eq!((header.file_length, header.trailing_bytes), (bytes.len() as u64, 0));
+
+        // Starting partway into a stream and with bytes after the mesh
+        let mut padded = b"junk".to_vec();
+        padded.append(&mut bytes);
+        padded.extend_from_slice(&[0; 5]);
+        let mut stream = Cursor::new(&padded);
+        stream.set_position(4);
+        let scanned = scan_mesh_header(&mut stream).unwrap();
+        assert_eq!(scanned.sub_meshes, header.sub_meshes);
+        assert_eq!((scanned.file_length, scanned.trailing_bytes), (header.file_length + 5, 5));
+    }
+
+    #[test]
+    fn truncated_files_fail() {
+        let bytes = fixture();
+        // Inside the headers, the skipped vertex block, the skipped index block and the sub meshes
+        for length in [7, 100, bytes.len() - 200, bytes.len() - 1] {
+            assert!(scan_mesh_header(&mut Cursor::new(&bytes[..length])).is_err(), "{length}");
+        }
+        assert!(matches!(scan_mesh_header(&mut Cursor::new(b"hsem")), Err(StormworksParserError::NotMesh)));
+    }
+}
@@ -0,0 +1,72 @@
+use alloc::vec::Vec;
+
+use crate::{StormworksMesh, StormworksMeshVertexRecord, StormworksParserError, StormworksSubMesh};
+
+// Deserialized as-is first, then only turned into a StormworksMesh if it passes the same checks a parsed .mesh file does
+#[derive(serde::Deserialize)]
+#[serde(rename = "StormworksMesh")]
+pub(crate) struct UncheckedStormworksMesh {
+    header0: u16,
+    header1: u16,
+    header3: u16,
+    header4: u16,
+    vertex_count: u32,
+    vertices: Vec<StormworksMeshVertexRecord>,
+    index_count: u32,
+    indices: Vec<u32>,
+    sub_mesh_count: u32,
+    sub_meshes: Vec<StormworksSubMesh>,
+}
+
+impl TryFrom<UncheckedStormworksMesh> for StormworksMesh {
+    type Error = StormworksParserError;
+
+    fn try_from(unchecked: UncheckedStormworksMesh) -> Result<Self, Self::Error> {
+        let mesh = StormworksMesh {
+            header0: unchecked.header0,
+            header1: unchecked.header1,
+            header3: unchecked.header3,
+            header4: unchecked.header4,
+            vertex_count: unchecked.vertex_count,
+            vertices: unchecked.vertices,
+            index_count: unchecked.index_count,
+            indices: unchecked.indices,
+            sub_mesh_count: unchecked.sub_mesh_count,
+            sub_meshes: unchecked.sub_meshes,
+        };
+        mesh.validate()?;
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use crate::{primitive_box, PrimitiveStyle, StormworksMesh, StormworksShaderType};
+    use vek::vec::repr_c::vec3::Vec3;
+
+    #[test]
+    fn valid_meshes_round_trip() {
+        let style = PrimitiveStyle { shader: StormworksShaderType::Unknown(7), ..Default::default() };
+        let mesh = primitive_box(Vec3::new(1, 2, 3), style).unwrap();
+        let json = serde_json::to_string(&mesh).unwrap();
+        assert_eq!(serde_json::from_str::<StormworksMesh>(&json).unwrap(), mesh);
+    }
+
+    #[test]
+    fn invalid_meshes_are_rejected() {
+        let mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        let mut value = serde_json::to_value(&mesh).unwrap();
+        value["indices"][0] = mesh.vertex_count.into();
+        assert!(serde_json::from_value::<StormworksMesh>(value).is_err());
+
+        let mut value = serde_json::to_value(&mesh).unwrap();
+        value["index_count"] = (mesh.index_count + 3).into();
+        assert!(serde_json::from_value::<StormworksMesh>(value).is_err());
+
+        let mut value = serde_json::to_value(&mesh).unwrap();
+        value["sub_meshes"][0]["index_buffer_length"] = u32::MAX.into();
+        let err = serde_json::from_value::<StormworksMesh>(value).unwrap_err();
+        assert!(err.to_string().contains("Submesh 0"), "{err}");
+    }
+}
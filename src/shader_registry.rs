@@ -0,0 +1,81 @@
+use alloc::{collections::BTreeMap, string::String};
+
+use crate::StormworksShaderType;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StormworksShaderInfo {
+    pub name: String,
+    pub description: String,
+}
+
+// Names and descriptions for shader ids, so ids added by game updates can be given meaning without waiting on a new variant.
+// Starts out knowing the four built in shaders.
+#[derive(Clone, Debug)]
+pub struct StormworksShaderRegistry {
+    shaders: BTreeMap<u16, StormworksShaderInfo>,
+}
+impl Default for StormworksShaderRegistry {
+    fn default() -> Self {
+        let mut registry = StormworksShaderRegistry { shaders: BTreeMap::new() };
+        registry.register(0, "opaque", "Regular lit, opaque surface");
+        registry.register(1, "transparent", "Alpha blended surface, like glass");
+        registry.register(2, "emissive", "Surface that glows in its vertex color");
+        registry.register(3, "lava", "Animated lava surface");
+        registry
+    }
+}
+impl StormworksShaderRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Registering an id that already has an entry replaces it
+    pub fn register(&mut self, id: u16, name: impl Into<String>, description: impl Into<String>) {
+        self.shaders.insert(id, StormworksShaderInfo { name: name.into(), description: description.into() });
+    }
+
+    pub fn info(&self, shader: StormworksShaderType) -> Option<&StormworksShaderInfo> {
+        self.shaders.get(&shader.to_u16())
+    }
+
+    pub fn name(&self, shader: StormworksShaderType) -> Option<&str> {
+        self.info(shader).map(|info| info.name.as_str())
+    }
+
+    pub fn description(&self, shader: StormworksShaderType) -> Option<&str> {
+        self.info(shader).map(|info| info.description.as_str())
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<StormworksShaderType> {
+        self.shaders
+            .iter()
+            .find(|(_, info)| info.name == name)
+            .map(|(&id, _)| StormworksShaderType::from_u16(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knows_the_built_in_shaders() {
+        let registry = StormworksShaderRegistry::new();
+        assert_eq!(registry.name(StormworksShaderType::Opaque), Some("opaque"));
+        assert_eq!(registry.name(StormworksShaderType::Lava), Some("lava"));
+        assert_eq!(registry.by_name("emissive"), Some(StormworksShaderType::Emissive));
+        assert_eq!(registry.info(StormworksShaderType::Unknown(9)), None);
+        assert_eq!(registry.by_name("hologram"), None);
+    }
+
+    #[test]
+    fn registers_new_and_replaced_ids() {
+        let mut registry = StormworksShaderRegistry::new();
+        registry.register(9, "hologram", "Flickering see through surface");
+        registry.register(0, "solid", "Renamed");
+        assert_eq!(registry.by_name("hologram"), Some(StormworksShaderType::Unknown(9)));
+        assert_eq!(registry.description(StormworksShaderType::Unknown(9)), Some("Flickering see through surface"));
+        assert_eq!(registry.name(StormworksShaderType::Opaque), Some("solid"));
+        assert_eq!(registry.by_name("opaque"), None);
+    }
+}
@@ -0,0 +1,394 @@
+use alloc::{format, string::{String, ToString}, vec::Vec};
+use core::fmt::Write;
+
+use vek::{vec::repr_c::vec3::Vec3, Rgba};
+
+use crate::{check_format_limit, InvalidMeshText, MAX_INDEX_RESERVATION, StormworksMesh, StormworksMeshVertexRecord, StormworksParserError, StormworksShaderType, StormworksSubMesh};
+
+// Line based text form of a .mesh, meant to be diffed and reviewed. Looks like:
+//
+// stormworks mesh text 1
+// header 7 1 19 0
+// vertices 3
+// vertex 0 position 0.0 0.0 0.0 color 255 255 255 255 normal 0.0 1.0 0.0
+// ...
+// indices 3
+// 0 1 2
+// sub_meshes 1
+// sub_mesh "name"
+// range 0 3
+// ...
+//
+// Every value of StormworksMesh is written, so parsing it back gives the exact same mesh.
+const TEXT_MAGIC: &str = "stormworks mesh text 1";
+
+// Debug formatting is the shortest representation that parses back to the same f32, NaN just needs its bits kept
+fn write_f32(out: &mut String, value: f32) {
+    if value.is_nan() {
+        let _ = write!(out, "nan:{:08x}", value.to_bits());
+    } else {
+        let _ = write!(out, "{value:?}");
+    }
+}
+
+fn write_vec3(out: &mut String, vec: Vec3<f32>) {
+    write_f32(out, vec.x);
+    out.push(' ');
+    write_f32(out, vec.y);
+    out.push(' ');
+    write_f32(out, vec.z);
+}
+
+fn write_quoted(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn shader_text_name(shader: &StormworksShaderType) -> String {
+    match shader {
+        StormworksShaderType::Opaque => "opaque".to_string(),
+        StormworksShaderType::Transparent => "transparent".to_string(),
+        StormworksShaderType::Emissive => "emissive".to_string(),
+        StormworksShaderType::Lava => "lava".to_string(),
+        StormworksShaderType::Unknown(id) => format!("unknown:{id}"),
+    }
+}
+
+struct TextParser<'a> {
+    lines: core::iter::Enumerate<core::str::Lines<'a>>,
+    line: usize,
+}
+impl<'a> TextParser<'a> {
+    fn new(text: &'a str) -> Self {
+        TextParser { lines: text.lines().enumerate(), line: 0 }
+    }
+
+    fn error(&self, reason: impl Into<String>) -> InvalidMeshText {
+        InvalidMeshText { line: self.line, reason: reason.into() }
+    }
+
+    // Next non blank line, with surrounding whitespace removed
+    fn next_line(&mut self) -> Result<&'a str, InvalidMeshText> {
+        for (i, line) in self.lines.by_ref() {
+            self.line = i + 1;
+            let line = line.trim();
+            if !line.is_empty() {
+                return Ok(line);
+            }
+        }
+        Err(self.error("unexpected end of text"))
+    }
+
+    // Next line, which has to start with `keyword`. Returns the rest of the line.
+    fn keyword_line(&mut self, keyword: &str) -> Result<&'a str, InvalidMeshText> {
+        let line = self.next_line()?;
+        match line.strip_prefix(keyword) {
+            Some(rest) if rest.is_empty() || rest.starts_with(' ') => Ok(rest.trim_start()),
+            _ => Err(self.error(format!("expected `{keyword}`"))),
+        }
+    }
+
+    fn finish(&mut self) -> Result<(), InvalidMeshText> {
+        match self.next_line() {
+            Ok(_) => Err(self.error("unexpected text after the last sub mesh")),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+struct Tokens<'a, 'p> {
+    parser: &'p TextParser<'a>,
+    tokens: core::str::SplitWhitespace<'a>,
+}
+impl<'a, 'p> Tokens<'a, 'p> {
+    fn new(parser: &'p TextParser<'a>, rest: &'a str) -> Self {
+        Tokens { parser, tokens: rest.split_whitespace() }
+    }
+
+    fn token(&mut self) -> Result<&'a str, InvalidMeshText> {
+        self.tokens.next().ok_or_else(|| self.parser.error("line ends too early"))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), InvalidMeshText> {
+        if self.token()? == keyword {
+            Ok(())
+        } else {
+            Err(self.parser.error(format!("expected `{keyword}`")))
+        }
+    }
+
+    fn number<T: core::str::FromStr>(&mut self) -> Result<T, InvalidMeshText> {
+        let token = self.token()?;
+        token.parse().map_err(|_| self.parser.error(format!("`{token}` isn't a valid number here")))
+    }
+
+    fn f32(&mut self) -> Result<f32, InvalidMeshText> {
+        let token = self.token()?;
+        let parsed = match token.strip_prefix("nan:") {
+            Some(bits) => u32::from_str_radix(bits, 16).ok().map(f32::from_bits),
+            None => token.parse().ok(),
+        };
+        parsed.ok_or_else(|| self.parser.error(format!("`{token}` isn't a valid float")))
+    }
+
+    fn vec3(&mut self) -> Result<Vec3<f32>, InvalidMeshText> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    fn end(&mut self) -> Result<(), InvalidMeshText> {
+        match self.tokens.next() {
+            None => Ok(()),
+            Some(token) => Err(self.parser.error(format!("unexpected `{token}` at end of line"))),
+        }
+    }
+}
+
+fn parse_quoted(parser: &TextParser, rest: &str) -> Result<String, InvalidMeshText> {
+    let inner = rest
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .ok_or_else(|| parser.error("expected a quoted name"))?;
+
+    let mut name = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => name.push(match chars.next() {
+                Some('"') => '"',
+                Some('\\') => '\\',
+                Some('n') => '\n',
+                Some('r') => '\r',
+                Some('t') => '\t',
+                _ => return Err(parser.error("invalid escape in name")),
+            }),
+            '"' => return Err(parser.error("unescaped quote in name")),
+            c => name.push(c),
+        }
+    }
+    Ok(name)
+}
+
+fn parse_shader(parser: &TextParser, token: &str) -> Result<StormworksShaderType, InvalidMeshText> {
+    match token {
+        "opaque" => Ok(StormworksShaderType::Opaque),
+        "transparent" => Ok(StormworksShaderType::Transparent),
+        "emissive" => Ok(StormworksShaderType::Emissive),
+        "lava" => Ok(StormworksShaderType::Lava),
+        _ => token
+            .strip_prefix("unknown:")
+            .and_then(|id| id.parse().ok())
+            .map(StormworksShaderType::Unknown)
+            .ok_or_else(|| parser.error(format!("unknown shader `{token}`"))),
+    }
+}
+
+impl StormworksMesh {
+    pub fn to_text(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{TEXT_MAGIC}");
+        let _ = writeln!(out, "header {} {} {} {}", self.header0, self.header1, self.header3, self.header4);
+
+        let _ = writeln!(out, "vertices {}", self.vertex_count);
+        for (i, vertex) in self.vertices.iter().enumerate() {
+            let _ = write!(out, "vertex {i} position ");
+            write_vec3(&mut out, vertex.position);
+            let _ = write!(out, " color {} {} {} {} normal ", vertex.color.r, vertex.color.g, vertex.color.b, vertex.color.a);
+            write_vec3(&mut out, vertex.normal);
+            out.push('\n');
+        }
+
+        // One triangle per line, so a changed triangle is a changed line
+        let _ = writeln!(out, "indices {}", self.index_count);
+        for triangle in self.indices.chunks(3) {
+            let line: Vec<String> = triangle.iter().map(|index| index.to_string()).collect();
+            let _ = writeln!(out, "{}", line.join(" "));
+        }
+
+        let _ = writeln!(out, "sub_meshes {}", self.sub_mesh_count);
+        for sub_mesh in &self.sub_meshes {
+            out.push_str("sub_mesh ");
+            write_quoted(&mut out, &sub_mesh.name);
+            let _ = writeln!(out, "\nname_length_bytes {}", sub_mesh.name_length_bytes);
+            let _ = writeln!(out, "range {} {}", sub_mesh.index_buffer_start, sub_mesh.index_buffer_length);
+            let _ = writeln!(out, "shader {}", shader_text_name(&sub_mesh.shader_id));
+            out.push_str("bounds ");
+            write_vec3(&mut out, sub_mesh.bounds_min);
+            out.push(' ');
+            write_vec3(&mut out, sub_mesh.bounds_max);
+            let _ = writeln!(out, "\nheader2 {}", sub_mesh.header2);
+            let _ = writeln!(out, "header6 {}", sub_mesh.header6);
+            out.push_str("header8 ");
+            write_vec3(&mut out, sub_mesh.header8);
+            out.push('\n');
+        }
+
+        out
+    }
+
+    // Parses the output of to_text back into the exact mesh it was made from
+    pub fn from_text(text: &str) -> Result<StormworksMesh, StormworksParserError> {
+        let mut parser = TextParser::new(text);
+
+        if parser.next_line().ok() != Some(TEXT_MAGIC) {
+            return Err(StormworksParserError::NotMesh);
+        }
+
+        let rest = parser.keyword_line("header")?;
+        let mut tokens = Tokens::new(&parser, rest);
+        let (header0, header1, header3, header4) = (tokens.number()?, tokens.number()?, tokens.number()?, tokens.number()?);
+        tokens.end()?;
+
+        let rest = parser.keyword_line("vertices")?;
+        let vertex_count: u32 = rest.parse().map_err(|_| parser.error("invalid vertex count"))?;
+        // Checked before anything is reserved, validate() would only catch it after a huge allocation
+        check_format_limit("vertex_count", vertex_count, u16::MAX as u32)?;
+        let mut vertices = Vec::with_capacity(vertex_count as usize);
+        for i in 0..vertex_count {
+            let rest = parser.keyword_line("vertex")?;
+            let mut tokens = Tokens::new(&parser, rest);
+            if tokens.number::<u32>()? != i {
+                return Err(parser.error(format!("expected vertex {i}")).into());
+            }
+            tokens.keyword("position")?;
+            let position = tokens.vec3()?;
+            tokens.keyword("color")?;
+            let color = Rgba::new(tokens.number()?, tokens.number()?, tokens.number()?, tokens.number()?);
+            tokens.keyword("normal")?;
+            let normal = tokens.vec3()?;
+            tokens.end()?;
+            vertices.push(StormworksMeshVertexRecord { position, color, normal });
+        }
+
+        let rest = parser.keyword_line("indices")?;
+        let index_count: u32 = rest.parse().map_err(|_| parser.error("invalid index count"))?;
+        let mut indices = Vec::with_capacity(index_count.min(MAX_INDEX_RESERVATION) as usize);
+        while indices.len() < index_count as usize {
+            let line = parser.next_line()?;
+            for token in line.split_whitespace() {
+                indices.push(token.parse().map_err(|_| parser.error(format!("`{token}` isn't a valid index")))?);
+            }
+        }
+        if indices.len() != index_count as usize {
+            return Err(parser.error(format!("expected {index_count} indices, found {}", indices.len())).into());
+        }
+
+        let rest = parser.keyword_line("sub_meshes")?;
+        let sub_mesh_count: u32 = rest.parse().map_err(|_| parser.error("invalid sub mesh count"))?;
+        check_format_limit("sub_mesh_count", sub_mesh_count, u16::MAX as u32)?;
+        let mut sub_meshes = Vec::with_capacity(sub_mesh_count as usize);
+        for _ in 0..sub_mesh_count {
+            let rest = parser.keyword_line("sub_mesh")?;
+            let name = parse_quoted(&parser, rest)?;
+
+            let rest = parser.keyword_line("name_length_bytes")?;
+            let name_length_bytes = rest.parse().map_err(|_| parser.error("invalid name length"))?;
+
+            let rest = parser.keyword_line("range")?;
+            let mut tokens = Tokens::new(&parser, rest);
+            let (index_buffer_start, index_buffer_length) = (tokens.number()?, tokens.number()?);
+            tokens.end()?;
+
+            let rest = parser.keyword_line("shader")?;
+            let shader_id = parse_shader(&parser, rest)?;
+
+            let rest = parser.keyword_line("bounds")?;
+            let mut tokens = Tokens::new(&parser, rest);
+            let (bounds_min, bounds_max) = (tokens.vec3()?, tokens.vec3()?);
+            tokens.end()?;
+
+            let rest = parser.keyword_line("header2")?;
+            let header2 = rest.parse().map_err(|_| parser.error("invalid header2"))?;
+
+            let rest = parser.keyword_line("header6")?;
+            let header6 = rest.parse().map_err(|_| parser.error("invalid header6"))?;
+
+            let rest = parser.keyword_line("header8")?;
+            let mut tokens = Tokens::new(&parser, rest);
+            let header8 = tokens.vec3()?;
+            tokens.end()?;
+
+            sub_meshes.push(StormworksSubMesh {
+                index_buffer_start,
+                index_buffer_length,
+                header2,
+                shader_id,
+                bounds_min,
+                bounds_max,
+                header6,
+                name_length_bytes,
+                name,
+                header8,
+            });
+        }
+        parser.finish()?;
+
+        let mesh = StormworksMesh {
+            header0,
+            header1,
+            header3,
+            header4,
+            vertex_count,
+            vertices,
+            index_count,
+            indices,
+            sub_mesh_count,
+            sub_meshes,
+        };
+        // Same checks build_stormworks_mesh does, so both ways of loading give equally valid meshes
+        mesh.validate()?;
+        Ok(mesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{primitive_box, PrimitiveStyle};
+
+    #[test]
+    fn round_trips_through_text() {
+        let mut mesh = primitive_box(Vec3::new(1, 2, 3), PrimitiveStyle::default()).unwrap();
+        mesh.vertices[0].normal.x = f32::from_bits(0x7fc0_1234);
+        mesh.sub_meshes[0].name = "quote \" slash \\ newline \n".into();
+        mesh.sub_meshes[0].name_length_bytes = mesh.sub_meshes[0].name.len() as u16;
+        mesh.sub_meshes[0].shader_id = StormworksShaderType::Unknown(42);
+
+        let text = mesh.to_text();
+        let parsed = StormworksMesh::from_text(&text).unwrap();
+        assert_eq!(parsed.to_text(), text);
+        assert_eq!(parsed.vertices[0].normal.x.to_bits(), 0x7fc0_1234);
+        assert_eq!(parsed.sub_meshes, mesh.sub_meshes);
+        assert_eq!(parsed.indices, mesh.indices);
+    }
+
+    #[test]
+    fn rejects_huge_counts_before_allocating() {
+        let vertices = format!("{TEXT_MAGIC}\nheader 7 1 19 0\nvertices 4000000000\n");
+        let err = StormworksMesh::from_text(&vertices).unwrap_err();
+        assert!(format!("{err:?}").contains("vertex_count"), "{err:?}");
+
+        let sub_meshes = format!("{TEXT_MAGIC}\nheader 7 1 19 0\nvertices 0\nindices 0\nsub_meshes 4000000000\n");
+        let err = StormworksMesh::from_text(&sub_meshes).unwrap_err();
+        assert!(format!("{err:?}").contains("sub_mesh_count"), "{err:?}");
+
+        // Not capped by the format, but the text runs out long before 4 billion indices
+        let indices = format!("{TEXT_MAGIC}\nheader 7 1 19 0\nvertices 0\nindices 4000000000\n0 0 0\n");
+        assert!(matches!(StormworksMesh::from_text(&indices), Err(StormworksParserError::CorruptFile(_))));
+    }
+
+    #[test]
+    fn rejects_counts_that_dont_match() {
+        let text = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap().to_text().replace("vertices 24", "vertices 25");
+        assert!(matches!(StormworksMesh::from_text(&text), Err(StormworksParserError::CorruptFile(_))));
+    }
+}
@@ -0,0 +1,177 @@
+use alloc::vec::Vec;
+
+use vek::{mat::repr_c::{mat3::Mat3, mat4::Mat4}, quaternion::repr_c::Quaternion, vec::repr_c::vec3::Vec3};
+
+use crate::{clamped_index_range, Aabb, StormworksMesh, StormworksMeshVertexRecord};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+// Which point center_on moves to the origin. Y is up, so the bottom is the lowest Y.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MeshAnchor {
+    Point(Vec3<f32>),
+    BoundsCenter,
+    BottomCenter,
+}
+
+// Positions by the matrix, normals by its cofactor matrix so they stay perpendicular under non uniform scale.
+// That's the inverse transpose times the determinant, so unlike the inverse it still exists for flattening transforms.
+pub(crate) fn transformed_vertices(vertices: &[StormworksMeshVertexRecord], transform: Mat4<f32>) -> Vec<StormworksMeshVertexRecord> {
+    let linear = Mat3::from(transform);
+    let (x, y, z) = (linear.cols.x, linear.cols.y, linear.cols.z);
+    // Mirroring flips the cofactor normals inward, the rewound triangles face the other way
+    let sign = if linear.determinant() < 0.0 { -1.0 } else { 1.0 };
+    let cofactor = Mat3::from_col_arrays([y.cross(z).into_array(), z.cross(x).into_array(), x.cross(y).into_array()]) * sign;
+    vertices
+        .iter()
+        .map(|vertex| {
+            let normal = cofactor * vertex.normal;
+            let length = normal.magnitude();
+            StormworksMeshVertexRecord {
+                position: transform.mul_point(vertex.position),
+                color: vertex.color,
+                // Faces squashed edge on have no direction left, they keep the one they had
+                normal: if length > 0.0 && length.is_finite() { normal / length } else { vertex.normal.try_normalized().unwrap_or(vertex.normal) },
+            }
+        })
+        .collect()
+}
+
+// A mirroring transform turns front faces into back faces unless the triangles are rewound
+pub(crate) fn mirrors(transform: Mat4<f32>) -> bool {
+    Mat3::from(transform).determinant() < 0.0
+}
+
+impl StormworksMesh {
+    // Affine transforms only, the projective row is ignored.
+    // Sub mesh bounds are recomputed from the moved vertices, so they stay tight under rotation.
+    pub fn transform(&mut self, transform: Mat4<f32>) {
+        self.vertices = transformed_vertices(&self.vertices, transform);
+        if mirrors(transform) {
+            for triangle in self.indices.chunks_exact_mut(3) {
+                triangle.swap(1, 2);
+            }
+        }
+
+        for sub_mesh in &mut self.sub_meshes {
+            let positions = self.indices[clamped_index_range(sub_mesh, self.indices.len())].iter().filter_map(|&index| self.vertices.get(index as usize)).map(|vertex| vertex.position);
+
+            if let Some(aabb) = Aabb::from_points(positions) {
+                sub_mesh.bounds_min = aabb.min;
+                sub_mesh.bounds_max = aabb.max;
+            } else {
+                // Nothing to measure, so the stored box itself gets moved
+                let corners = sub_mesh.stored_aabb().corners().map(|corner| transform.mul_point(corner));
+                if let Some(moved) = Aabb::from_points(corners) {
+                    sub_mesh.bounds_min = moved.min;
+                    sub_mesh.bounds_max = moved.max;
+                }
+            }
+        }
+    }
+
+    pub fn translate(&mut self, offset: Vec3<f32>) {
+        self.transform(Mat4::translation_3d(offset));
+    }
+
+    // Negative factors mirror, and flip the winding to match
+    pub fn scale(&mut self, factors: Vec3<f32>) {
+        self.transform(Mat4::scaling_3d(factors));
+    }
+
+    pub fn rotate(&mut self, rotation: Quaternion<f32>) {
+        self.transform(Mat4::from(rotation));
+    }
+
+    // Mirrors across the plane through the origin perpendicular to axis, like the game's mirror symmetric parts
+    pub fn mirror(&mut self, axis: Axis) {
+        let factors = match axis {
+            Axis::X => Vec3::new(-1.0, 1.0, 1.0),
+            Axis::Y => Vec3::new(1.0, -1.0, 1.0),
+            Axis::Z => Vec3::new(1.0, 1.0, -1.0),
+        };
+        self.scale(factors);
+    }
+
+    // Moves the mesh so the anchor ends up at the origin, and returns the offset that was applied.
+    // Bounds are those of the vertices, an empty mesh isn't moved.
+    pub fn center_on(&mut self, anchor: MeshAnchor) -> Vec3<f32> {
+        let point = match (anchor, self.aabb()) {
+            (MeshAnchor::Point(point), _) => point,
+            (_, None) => return Vec3::zero(),
+            (MeshAnchor::BoundsCenter, Some(aabb)) => aabb.center(),
+            (MeshAnchor::BottomCenter, Some(aabb)) => Vec3::new(aabb.center().x, aabb.min.y, aabb.center().z),
+        };
+        self.translate(-point);
+        -point
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{normals::triangle_normal, primitive_box, PrimitiveStyle};
+
+    fn assert_close(a: Vec3<f32>, b: Vec3<f32>) {
+        assert!(a.distance(b) < 1e-5, "{a:?} != {b:?}");
+    }
+
+    // Every stored normal should point the way its triangle faces, as wound
+    fn assert_normals_match_winding(mesh: &StormworksMesh) {
+        for triangle in mesh.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|k| &mesh.vertices[triangle[k] as usize]);
+            let face = triangle_normal(a.position, b.position, c.position).normalized();
+            for corner in [a, b, c] {
+                assert_close(corner.normal, face);
+            }
+        }
+    }
+
+    #[test]
+    fn mirror_flips_winding_and_normals() {
+        let original = primitive_box(Vec3::new(2, 1, 1), PrimitiveStyle::default()).unwrap();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let mut mesh = original.clone();
+            mesh.mirror(axis);
+            assert_normals_match_winding(&mesh);
+            assert_eq!(mesh.indices[..3], [original.indices[0], original.indices[2], original.indices[1]]);
+            assert_eq!(mesh.mass_properties(1.0).unwrap().volume, original.mass_properties(1.0).unwrap().volume);
+        }
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_non_uniform_scale() {
+        let mut mesh = primitive_box(Vec3::new(1, 1, 1), PrimitiveStyle::default()).unwrap();
+        mesh.transform(Mat4::rotation_y(0.5) * Mat4::scaling_3d(Vec3::new(3.0, 0.5, -2.0)) * Mat4::rotation_x(0.3));
+        assert_normals_match_winding(&mesh);
+    }
+
+    #[test]
+    fn flattening_scale_keeps_normals_finite() {
+        let mut mesh = primitive_box(Vec3::new(1, 1, 1), PrimitiveStyle::default()).unwrap();
+        let top = mesh.vertices.iter().position(|vertex| vertex.normal == Vec3::unit_y()).unwrap();
+        mesh.scale(Vec3::new(1.0, 0.0, 1.0));
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal.map(f32::is_finite).reduce_and() && (vertex.normal.magnitude() - 1.0).abs() < 1e-5));
+        assert_close(mesh.vertices[top].normal, Vec3::unit_y());
+
+        mesh.scale(Vec3::zero());
+        assert!(mesh.vertices.iter().all(|vertex| vertex.normal.map(f32::is_finite).reduce_and()));
+    }
+
+    #[test]
+    fn sub_mesh_bounds_follow_the_vertices() {
+        let mut mesh = primitive_box(Vec3::new(4, 4, 4), PrimitiveStyle::default()).unwrap();
+        mesh.translate(Vec3::new(1.0, 2.0, 3.0));
+        assert_close(mesh.sub_meshes[0].bounds_min, Vec3::new(0.875, 1.875, 2.875));
+        assert_close(mesh.sub_meshes[0].bounds_max, Vec3::new(1.875, 2.875, 3.875));
+
+        let offset = mesh.center_on(MeshAnchor::BottomCenter);
+        assert_close(offset, -Vec3::new(1.375, 1.875, 3.375));
+        assert_close(mesh.aabb().unwrap().min, Vec3::new(-0.5, 0.0, -0.5));
+    }
+}
@@ -0,0 +1,199 @@
+use std::fmt::Write;
+
+use crate::{clamped_index_range, StormworksMesh, StormworksShaderType};
+
+// USD wants linear display colors, the mesh stores sRGB bytes
+fn srgb_u8_to_linear(c: u8) -> f32 {
+    let c = c as f32 / 255.0;
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn usd_shader_token(shader: &StormworksShaderType) -> String {
+    match shader {
+        StormworksShaderType::Opaque => "opaque".to_string(),
+        StormworksShaderType::Transparent => "transparent".to_string(),
+        StormworksShaderType::Emissive => "emissive".to_string(),
+        StormworksShaderType::Lava => "lava".to_string(),
+        StormworksShaderType::Unknown(id) => format!("unknown_{id}"),
+    }
+}
+
+// USD identifiers are [A-Za-z_][A-Za-z0-9_]*, anything else becomes '_'
+pub(crate) fn sanitize_usd_identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+// Contents of a double quoted USDA string, which can't hold raw newlines or other control characters
+fn escape_usd_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if c.is_ascii_control() => {
+                let _ = write!(escaped, "\\x{:02x}", c as u32);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_array<T>(out: &mut String, items: impl IntoIterator<Item = T>, mut write_item: impl FnMut(&mut String, T)) {
+    out.push('[');
+    for (i, item) in items.into_iter().enumerate() {
+        if i != 0 {
+            out.push_str(", ");
+        }
+        write_item(out, item);
+    }
+    out.push(']');
+}
+
+impl StormworksMesh {
+    // Exports as a single UsdGeomMesh prim with one GeomSubset per sub mesh.
+    // Stormworks is left handed with clockwise front faces, so like the bevy conversion X is mirrored,
+    // which also turns the winding into the counter clockwise that USD's default rightHanded orientation expects.
+    pub fn to_usda(&self, prim_name: &str) -> String {
+        let prim_name = sanitize_usd_identifier(prim_name);
+        let triangle_count = self.indices.len() / 3;
+
+        let mut out = String::new();
+        // Writing into a String can't fail, so the fmt::Results are ignored throughout
+        let _ = writeln!(out, "#usda 1.0");
+        let _ = writeln!(out, "(\n    defaultPrim = \"{prim_name}\"\n    metersPerUnit = 1\n    upAxis = \"Y\"\n)\n");
+        let _ = writeln!(out, "def Mesh \"{prim_name}\"\n{{");
+
+        if let Some(first) = self.vertices.first() {
+            let (mut min, mut max) = (first.position, first.position);
+            for vertex in &self.vertices {
+                min = min.map2(vertex.position, f32::min);
+                max = max.map2(vertex.position, f32::max);
+            }
+            let _ = writeln!(out, "    float3[] extent = [({:?}, {:?}, {:?}), ({:?}, {:?}, {:?})]", -max.x, min.y, min.z, -min.x, max.y, max.z);
+        }
+
+        out.push_str("    int[] faceVertexCounts = ");
+        write_array(&mut out, 0..triangle_count, |out, _| out.push('3'));
+        out.push('\n');
+
+        out.push_str("    int[] faceVertexIndices = ");
+        write_array(&mut out, self.indices.chunks_exact(3), |out, triangle| {
+            let _ = write!(out, "{}, {}, {}", triangle[0], triangle[1], triangle[2]);
+        });
+        out.push('\n');
+
+        out.push_str("    normal3f[] normals = ");
+        write_array(&mut out, &self.vertices, |out, vertex| {
+            let _ = write!(out, "({:?}, {:?}, {:?})", -vertex.normal.x, vertex.normal.y, vertex.normal.z);
+        });
+        out.push_str(" (\n        interpolation = \"vertex\"\n    )\n");
+
+        out.push_str("    point3f[] points = ");
+        write_array(&mut out, &self.vertices, |out, vertex| {
+            let _ = write!(out, "({:?}, {:?}, {:?})", -vertex.position.x, vertex.position.y, vertex.position.z);
+        });
+        out.push('\n');
+
+        out.push_str("    color3f[] primvars:displayColor = ");
+        write_array(&mut out, &self.vertices, |out, vertex| {
+            let _ = write!(
+                out,
+                "({:?}, {:?}, {:?})",
+                srgb_u8_to_linear(vertex.color.r),
+                srgb_u8_to_linear(vertex.color.g),
+                srgb_u8_to_linear(vertex.color.b)
+            );
+        });
+        out.push_str(" (\n        interpolation = \"vertex\"\n    )\n");
+
+        out.push_str("    float[] primvars:displayOpacity = ");
+        write_array(&mut out, &self.vertices, |out, vertex| {
+            let _ = write!(out, "{:?}", vertex.color.a as f32 / 255.0);
+        });
+        out.push_str(" (\n        interpolation = \"vertex\"\n    )\n");
+
+        let _ = writeln!(out, "    uniform token subdivisionScheme = \"none\"");
+
+        let mut used_names: Vec<String> = Vec::with_capacity(self.sub_meshes.len());
+        for sub_mesh in &self.sub_meshes {
+            // Sub mesh names aren't guaranteed unique, but sibling prim names have to be
+            let base_name = sanitize_usd_identifier(&sub_mesh.name);
+            let mut subset_name = base_name.clone();
+            let mut suffix = 1;
+            while used_names.contains(&subset_name) {
+                subset_name = format!("{base_name}_{suffix}");
+                suffix += 1;
+            }
+
+            let range = clamped_index_range(sub_mesh, self.indices.len());
+            let (first_face, end_face) = (range.start / 3, (range.end / 3).min(triangle_count));
+
+            let _ = writeln!(out, "\n    def GeomSubset \"{subset_name}\"\n    {{");
+            let _ = writeln!(out, "        uniform token elementType = \"face\"");
+            let _ = writeln!(out, "        uniform token familyName = \"materialBind\"");
+            out.push_str("        int[] indices = ");
+            write_array(&mut out, first_face..end_face.max(first_face), |out, face| {
+                let _ = write!(out, "{face}");
+            });
+            out.push('\n');
+            let _ = writeln!(out, "        custom token stormworks:shader = \"{}\"", usd_shader_token(&sub_mesh.shader_id));
+            let _ = writeln!(out, "        custom string stormworks:name = \"{}\"", escape_usd_string(&sub_mesh.name));
+            let _ = writeln!(out, "    }}");
+
+            used_names.push(subset_name);
+        }
+
+        let _ = writeln!(out, "}}");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StormworksSubMesh;
+    use vek::vec::repr_c::vec3::Vec3;
+
+    #[test]
+    fn escapes_control_characters_in_names() {
+        assert_eq!(escape_usd_string("a\"b\\c\nd\re\tf\u{1}g"), "a\\\"b\\\\c\\nd\\re\\tf\\x01g");
+    }
+
+    #[test]
+    fn overflowing_sub_mesh_range_doesnt_panic() {
+        let mesh = StormworksMesh {
+            sub_mesh_count: 1,
+            sub_meshes: vec![StormworksSubMesh {
+                index_buffer_start: u32::MAX,
+                index_buffer_length: u32::MAX,
+                header2: 0,
+                shader_id: StormworksShaderType::Opaque,
+                bounds_min: Vec3::zero(),
+                bounds_max: Vec3::zero(),
+                header6: 0,
+                name_length_bytes: 5,
+                name: "a\nb\"c".into(),
+                header8: Vec3::zero(),
+            }],
+            ..Default::default()
+        };
+        let usda = mesh.to_usda("mesh");
+        assert!(usda.contains("int[] indices = []"));
+        assert!(usda.contains("custom string stormworks:name = \"a\\nb\\\"c\""));
+    }
+}
@@ -0,0 +1,74 @@
+use vek::{vec::repr_c::vec3::Vec3, Rgba};
+
+use crate::{StormworksMesh, StormworksMeshVertexRecord};
+
+// Borrowed views over one attribute of a vertex slice.
+// The glam, nalgebra and mint features add From impls turning them into Vecs of their own types,
+// e.g. `let positions: Vec<glam::Vec3> = mesh.positions().into();`
+#[derive(Clone, Copy, Debug)]
+pub struct VertexPositions<'a>(pub &'a [StormworksMeshVertexRecord]);
+
+#[derive(Clone, Copy, Debug)]
+pub struct VertexNormals<'a>(pub &'a [StormworksMeshVertexRecord]);
+
+// Colors are sRGB, exactly as stored in the file
+#[derive(Clone, Copy, Debug)]
+pub struct VertexColors<'a>(pub &'a [StormworksMeshVertexRecord]);
+
+impl<'a> VertexPositions<'a> {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Vec3<f32>> + 'a {
+        self.0.iter().map(|vertex| vertex.position)
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> VertexNormals<'a> {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Vec3<f32>> + 'a {
+        self.0.iter().map(|vertex| vertex.normal)
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<'a> VertexColors<'a> {
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = Rgba<u8>> + 'a {
+        self.0.iter().map(|vertex| vertex.color)
+    }
+    // 0 to 1 per channel, still sRGB
+    pub fn iter_f32(&self) -> impl ExactSizeIterator<Item = Rgba<f32>> + 'a {
+        self.0.iter().map(|vertex| vertex.color_f32())
+    }
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl StormworksMeshVertexRecord {
+    pub fn color_f32(&self) -> Rgba<f32> {
+        self.color.map(|channel| channel as f32 / 255.0)
+    }
+}
+
+impl StormworksMesh {
+    pub fn positions(&self) -> VertexPositions<'_> {
+        VertexPositions(&self.vertices)
+    }
+    pub fn normals(&self) -> VertexNormals<'_> {
+        VertexNormals(&self.vertices)
+    }
+    pub fn colors(&self) -> VertexColors<'_> {
+        VertexColors(&self.vertices)
+    }
+}
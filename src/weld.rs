@@ -0,0 +1,159 @@
+use alloc::{collections::BTreeMap, vec::Vec};
+
+use crate::{prelude::*, StormworksMesh, StormworksMeshVertexRecord, StormworksParserError};
+
+fn within(a: &StormworksMeshVertexRecord, b: &StormworksMeshVertexRecord, tolerance: f32) -> bool {
+    a.color == b.color
+        && a.position.distance_squared(b.position) <= tolerance * tolerance
+        && a.normal.distance_squared(b.normal) <= tolerance * tolerance
+}
+
+impl StormworksMesh {
+    // Merges vertices whose positions and normals are each within tolerance of each other and whose colors are equal.
+    // A vertex is merged into the first earlier vertex that matches, so order is kept and chains don't drift further than tolerance.
+    // Only indices change, sub mesh ranges stay as they were. Returns how many vertices were removed.
+    // Fails without changing anything if an index is out of range.
+    pub fn weld(&mut self, tolerance: f32) -> Result<usize,StormworksParserError> {
+        self.check_indices()?;
+        let tolerance = tolerance.max(0.0);
+        // Grid cells as big as the tolerance, so matches are always in the same or a neighbouring cell.
+        // With no tolerance at all the exact position is the cell.
+        let cell = |position: Vec3<f32>| -> [i64;3] {
+            if tolerance > 0.0 {
+                (position / tolerance).map(|c| c.floor() as i64).into_array()
+            } else {
+                (position + Vec3::zero()).map(|c| c.to_bits() as i64).into_array()
+            }
+        };
+        let reach = if tolerance > 0.0 { 1 } else { 0 };
+
+        let mut grid: BTreeMap<[i64;3], Vec<u32>> = BTreeMap::new();
+        let mut kept: Vec<StormworksMeshVertexRecord> = Vec::with_capacity(self.vertices.len());
+        let mut remap = Vec::with_capacity(self.vertices.len());
+
+        for vertex in self.vertices.drain(..) {
+            let [x, y, z] = cell(vertex.position);
+            // Every neighbouring cell is searched, the earliest match could be in any of them
+            let mut found: Option<u32> = None;
+            for dx in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dz in -reach..=reach {
+                        let key = [x.saturating_add(dx), y.saturating_add(dy), z.saturating_add(dz)];
+                        // Cells are filled in index order, so the first match in a cell is the earliest one there
+                        let candidate = grid.get(&key).and_then(|candidates| candidates.iter().copied().find(|&index| within(&kept[index as usize], &vertex, tolerance)));
+                        found = match (found, candidate) {
+                            (Some(found), Some(candidate)) => Some(found.min(candidate)),
+                            (found, candidate) => found.or(candidate),
+                        };
+                    }
+                }
+            }
+
+            remap.push(match found {
+                Some(index) => index,
+                None => {
+                    let index = kept.len() as u32;
+                    grid.entry([x, y, z]).or_default().push(index);
+                    kept.push(vertex);
+                    index
+                }
+            });
+        }
+
+        let removed = remap.len() - kept.len();
+        self.vertices = kept;
+        self.remap_indices(&remap);
+        Ok(removed)
+    }
+
+    // Drops vertices no index refers to. Returns how many were removed, or fails without changes if an index is out of range.
+    pub fn remove_unused_vertices(&mut self) -> Result<usize,StormworksParserError> {
+        self.check_indices()?;
+        let mut used = alloc::vec![false; self.vertices.len()];
+        for &index in &self.indices {
+            used[index as usize] = true;
+        }
+
+        let mut remap = Vec::with_capacity(self.vertices.len());
+        let mut next = 0;
+        for &used in &used {
+            remap.push(next);
+            if used {
+                next += 1;
+            }
+        }
+
+        let mut used = used.into_iter();
+        let before = self.vertices.len();
+        self.vertices.retain(|_| used.next().unwrap_or(false));
+        self.remap_indices(&remap);
+        Ok(before - self.vertices.len())
+    }
+
+    fn remap_indices(&mut self, remap: &[u32]) {
+        for index in &mut self.indices {
+            *index = remap[*index as usize];
+        }
+        self.vertex_count = self.vertices.len() as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vek::Rgba;
+
+    fn vertex(x: f32, y: f32, z: f32) -> StormworksMeshVertexRecord {
+        StormworksMeshVertexRecord { position: Vec3::new(x, y, z), color: Rgba::white(), normal: Vec3::unit_y() }
+    }
+
+    fn mesh(vertices: Vec<StormworksMeshVertexRecord>, indices: Vec<u32>) -> StormworksMesh {
+        StormworksMesh { vertex_count: vertices.len() as u32, vertices, index_count: indices.len() as u32, indices, ..Default::default() }
+    }
+
+    #[test]
+    fn merges_into_the_earliest_match() {
+        // Vertex 2 is within tolerance of both 0 and 1, which sit in different cells on either side of it
+        let mut mesh = mesh(alloc::vec![vertex(0.109, 0.0, 0.0), vertex(0.091, 0.0, 0.0), vertex(0.1, 0.0, 0.0)], alloc::vec![2, 1, 0]);
+        assert_eq!(mesh.weld(0.01).unwrap(), 1);
+        assert_eq!(mesh.vertices, alloc::vec![vertex(0.109, 0.0, 0.0), vertex(0.091, 0.0, 0.0)]);
+        assert_eq!(mesh.indices, alloc::vec![0, 1, 0]);
+        assert_eq!(mesh.vertex_count, 2);
+    }
+
+    #[test]
+    fn remaps_indices_of_duplicated_corners() {
+        // Two triangles of a quad, each with its own copy of the shared edge
+        let corners = [vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 0.0, 1.0)];
+        let mut mesh = mesh(corners.to_vec(), alloc::vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(mesh.weld(0.0).unwrap(), 2);
+        assert_eq!(mesh.indices, alloc::vec![0, 1, 2, 0, 2, 3]);
+        assert!(mesh.validate().is_ok());
+
+        // Different colors or normals keep vertices apart
+        let mut other = corners[0].clone();
+        other.color = Rgba::black();
+        let mut flipped = corners[0].clone();
+        flipped.normal = -flipped.normal;
+        let mut mesh = self::mesh(alloc::vec![corners[0].clone(), other, flipped], alloc::vec![0, 1, 2]);
+        assert_eq!(mesh.weld(0.5).unwrap(), 0);
+    }
+
+    #[test]
+    fn removes_unused_vertices() {
+        let mut mesh = mesh(alloc::vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 0.0), vertex(2.0, 0.0, 0.0), vertex(3.0, 0.0, 0.0)], alloc::vec![3, 1, 3]);
+        assert_eq!(mesh.remove_unused_vertices().unwrap(), 2);
+        assert_eq!(mesh.vertices, alloc::vec![vertex(1.0, 0.0, 0.0), vertex(3.0, 0.0, 0.0)]);
+        assert_eq!(mesh.indices, alloc::vec![1, 0, 1]);
+        assert_eq!(mesh.vertex_count, 2);
+    }
+
+    #[test]
+    fn out_of_range_indices_fail_without_changes() {
+        let mut mesh = mesh(alloc::vec![vertex(0.0, 0.0, 0.0), vertex(0.0, 0.0, 0.0)], alloc::vec![0, 1, 2]);
+        let before = mesh.clone();
+        assert!(mesh.weld(0.1).is_err());
+        assert!(mesh.remove_unused_vertices().is_err());
+        assert_eq!(mesh, before);
+    }
+}
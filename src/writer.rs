@@ -0,0 +1,116 @@
+use std::io::Write;
+
+use vek::vec::repr_c::vec3::Vec3;
+
+use crate::{detect_format_version, MeshFormatVersion, StormworksMesh, StormworksParseOptions, StormworksParserError, BYTES_PER_VERTEX};
+
+fn write_vec3_to(writer: &mut impl Write, vec: Vec3<f32>) -> Result<(),std::io::Error> {
+    writer.write_all(&vec.x.to_le_bytes())?;
+    writer.write_all(&vec.y.to_le_bytes())?;
+    writer.write_all(&vec.z.to_le_bytes())
+}
+
+// The reverse of build_stormworks_mesh, writes the exact layout it reads.
+// The mesh is validated first, so nothing gets written for a mesh that couldn't be parsed back.
+pub fn write_stormworks_mesh(mesh: &StormworksMesh, writer: &mut impl Write) -> Result<(),StormworksParserError> {
+    write_stormworks_mesh_with_options(mesh, writer, &StormworksParseOptions::default())
+}
+// The vertex layout is picked from the headers like parsing does, so a mesh read with a fallback_version writes back with the same options.
+// The stored headers are written unchanged.
+pub fn write_stormworks_mesh_with_options(mesh: &StormworksMesh, writer: &mut impl Write, options: &StormworksParseOptions) -> Result<(),StormworksParserError> {
+    mesh.validate()?;
+
+    match detect_format_version([mesh.header0, mesh.header1, mesh.header3, mesh.header4], options)? {
+        MeshFormatVersion::V7 => {}
+    }
+
+    writer.write_all(b"mesh")?;
+    writer.write_all(&mesh.header0.to_le_bytes())?;
+    writer.write_all(&mesh.header1.to_le_bytes())?;
+    writer.write_all(&(mesh.vertex_count as u16).to_le_bytes())?;
+    writer.write_all(&mesh.header3.to_le_bytes())?;
+    writer.write_all(&mesh.header4.to_le_bytes())?;
+
+    for vertex in &mesh.vertices {
+        let mut vertex_record_bytes = [0_u8;BYTES_PER_VERTEX];
+        vertex_record_bytes[0..=3].copy_from_slice(&vertex.position.x.to_le_bytes());
+        vertex_record_bytes[4..=7].copy_from_slice(&vertex.position.y.to_le_bytes());
+        vertex_record_bytes[8..=11].copy_from_slice(&vertex.position.z.to_le_bytes());
+        vertex_record_bytes[12..=15].copy_from_slice(&[vertex.color.r, vertex.color.g, vertex.color.b, vertex.color.a]);
+        vertex_record_bytes[16..=19].copy_from_slice(&vertex.normal.x.to_le_bytes());
+        vertex_record_bytes[20..=23].copy_from_slice(&vertex.normal.y.to_le_bytes());
+        vertex_record_bytes[24..=27].copy_from_slice(&vertex.normal.z.to_le_bytes());
+        writer.write_all(&vertex_record_bytes)?;
+    }
+
+    writer.write_all(&mesh.index_count.to_le_bytes())?;
+    for &index in &mesh.indices {
+        // validate made sure every index is below the u16 vertex count
+        writer.write_all(&(index as u16).to_le_bytes())?;
+    }
+
+    writer.write_all(&(mesh.sub_mesh_count as u16).to_le_bytes())?;
+    for sub_mesh in &mesh.sub_meshes {
+        writer.write_all(&sub_mesh.index_buffer_start.to_le_bytes())?;
+        writer.write_all(&sub_mesh.index_buffer_length.to_le_bytes())?;
+        writer.write_all(&sub_mesh.header2.to_le_bytes())?;
+        writer.write_all(&sub_mesh.shader_id.to_u16().to_le_bytes())?;
+        write_vec3_to(writer, sub_mesh.bounds_min)?;
+        write_vec3_to(writer, sub_mesh.bounds_max)?;
+        writer.write_all(&sub_mesh.header6.to_le_bytes())?;
+        writer.write_all(&sub_mesh.name_length_bytes.to_le_bytes())?;
+        writer.write_all(sub_mesh.name.as_bytes())?;
+        write_vec3_to(writer, sub_mesh.header8)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_stormworks_mesh, parse_stormworks_mesh_with_options, primitive_box, PrimitiveStyle, StormworksShaderType};
+
+    fn written(mesh: &StormworksMesh, options: &StormworksParseOptions) -> Result<Vec<u8>,StormworksParserError> {
+        let mut bytes = Vec::new();
+        write_stormworks_mesh_with_options(mesh, &mut bytes, options)?;
+        Ok(bytes)
+    }
+
+    #[test]
+    fn parse_write_parse_round_trips() {
+        let style = PrimitiveStyle { shader: StormworksShaderType::Unknown(9), ..Default::default() };
+        let mut mesh = primitive_box(Vec3::new(1, 2, 3), style).unwrap();
+        (mesh.header0, mesh.header1, mesh.header3, mesh.header4) = (8, 2, 0x13, 1);
+        let lenient = StormworksParseOptions { fallback_version: Some(MeshFormatVersion::V7), ..Default::default() };
+
+        let bytes = written(&mesh, &lenient).unwrap();
+        let parsed = parse_stormworks_mesh_with_options(&bytes, &lenient).unwrap();
+        assert_eq!(parsed, mesh);
+        assert_eq!(parsed.sub_meshes[0].shader_id, StormworksShaderType::Unknown(9));
+        assert_eq!(written(&parsed, &lenient).unwrap(), bytes);
+
+        // Without a fallback, unknown headers fail the same way they do when parsing
+        assert!(matches!(written(&mesh, &StormworksParseOptions::default()), Err(StormworksParserError::UnsupportedVersion { headers: [8, 2, 0x13, 1] })));
+    }
+
+    #[test]
+    fn default_and_v7_meshes_are_written() {
+        let bytes = written(&StormworksMesh::default(), &StormworksParseOptions::default()).unwrap();
+        assert_eq!(parse_stormworks_mesh(&bytes).unwrap(), StormworksMesh::default());
+
+        let mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        let mut bytes = Vec::new();
+        write_stormworks_mesh(&mesh, &mut bytes).unwrap();
+        assert_eq!(parse_stormworks_mesh(&bytes).unwrap(), mesh);
+    }
+
+    #[test]
+    fn invalid_meshes_write_nothing() {
+        let mut mesh = primitive_box(Vec3::one(), PrimitiveStyle::default()).unwrap();
+        mesh.indices[0] = mesh.vertex_count;
+        let mut bytes = Vec::new();
+        assert!(write_stormworks_mesh(&mesh, &mut bytes).is_err());
+        assert!(bytes.is_empty());
+    }
+}